@@ -0,0 +1,118 @@
+//! Allocate/deallocate throughput per workload for TLSF, a first fit list and
+//! the system allocator, plus a per call latency and fragmentation table
+//! printed before the timed runs.
+//!
+//! `SUBALLOC_TRACE=path` adds a recorded trace (see `common::trace` for the
+//! format) to the workloads.
+
+mod common;
+
+use common::{BenchAlloc, FirstFit, Profile, SystemAlloc, Workload};
+use criterion::measurement::WallTime;
+use criterion::{BenchmarkGroup, Criterion, Throughput, criterion_group, criterion_main};
+use suballoc::{SubAllocator, Word};
+
+const CAPACITY: Word = 64 << 20;
+const EVENTS: usize = 20_000;
+
+fn workloads() -> Vec<Workload> {
+    let mut workloads = vec![
+        common::uniform_small(EVENTS),
+        common::power_law(EVENTS),
+        common::fifo(EVENTS, 256),
+        common::lifo(EVENTS, 512),
+        common::random_lifetimes(EVENTS, 512),
+        common::trace("parser", include_str!("traces/parser.trace")),
+    ];
+    if let Ok(path) = std::env::var("SUBALLOC_TRACE") {
+        let text = std::fs::read_to_string(&path).unwrap();
+        workloads.push(common::trace("env", &text));
+    }
+    workloads
+}
+
+fn bench_replay<A: BenchAlloc>(
+    group: &mut BenchmarkGroup<'_, WallTime>,
+    name: &str,
+    mut alloc: A,
+    workload: &Workload,
+) {
+    let mut slots = vec![None; workload.ids];
+    group.bench_function(name, |b| {
+        b.iter(|| common::replay(&mut alloc, workload, &mut slots))
+    });
+}
+
+fn throughput(c: &mut Criterion) {
+    for workload in workloads() {
+        let mut group = c.benchmark_group(&workload.name);
+        group.throughput(Throughput::Elements(workload.events.len() as u64));
+        bench_replay(&mut group, "tlsf", SubAllocator::new(CAPACITY), &workload);
+        bench_replay(&mut group, "first_fit", FirstFit::new(CAPACITY), &workload);
+        bench_replay(&mut group, "system", SystemAlloc, &workload);
+        group.finish();
+    }
+}
+
+fn percentile(sorted: &[u64], p: f64) -> u64 {
+    match sorted.len() {
+        0 => 0,
+        n => sorted[((n - 1) as f64 * p).round() as usize],
+    }
+}
+
+fn latency_row(ns: &mut [u64]) -> String {
+    ns.sort_unstable();
+    let mean = ns.iter().sum::<u64>() as f64 / ns.len().max(1) as f64;
+    format!(
+        "{mean:>7.0} {:>6} {:>6} {:>7} {:>8}",
+        percentile(ns, 0.5),
+        percentile(ns, 0.99),
+        percentile(ns, 0.999),
+        ns.last().copied().unwrap_or(0)
+    )
+}
+
+fn print_profile(workload: &Workload, name: &str, mut profile: Profile) {
+    let frag = &profile.fragmentation;
+    let frag = match frag.is_empty() {
+        true => "       -      -".to_string(),
+        false => format!(
+            "{:>7.3} {:>6.3}",
+            frag.iter().sum::<f64>() / frag.len() as f64,
+            frag.iter().copied().fold(0.0, f64::max)
+        ),
+    };
+    println!(
+        "{:<18} {:<10} alloc {} | free {} | frag {} | failed {}",
+        workload.name,
+        name,
+        latency_row(&mut profile.alloc_ns),
+        latency_row(&mut profile.free_ns),
+        frag,
+        profile.failed
+    );
+}
+
+/// not timed by criterion: one profiled replay per workload and allocator
+fn latency_and_fragmentation(_: &mut Criterion) {
+    let ns = format!(
+        "{:>7} {:>6} {:>6} {:>7} {:>8}",
+        "mean", "p50", "p99", "p99.9", "max"
+    );
+    println!(
+        "{:<18} {:<10} alloc {ns} | free {ns} | frag {:>7} {:>6} | failed  (ns per call)",
+        "workload", "allocator", "mean", "max"
+    );
+    for workload in workloads() {
+        let profile = common::profile(&mut SubAllocator::new(CAPACITY), &workload);
+        print_profile(&workload, "tlsf", profile);
+        let profile = common::profile(&mut FirstFit::new(CAPACITY), &workload);
+        print_profile(&workload, "first_fit", profile);
+        let profile = common::profile(&mut SystemAlloc, &workload);
+        print_profile(&workload, "system", profile);
+    }
+}
+
+criterion_group!(benches, latency_and_fragmentation, throughput);
+criterion_main!(benches);
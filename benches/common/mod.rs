@@ -0,0 +1,350 @@
+//! Workload generators and the allocators the benches compare.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, BinaryHeap, HashMap};
+use std::ptr::NonNull;
+use std::time::Instant;
+use suballoc::{SubAllocator, Word};
+
+#[derive(Debug, Clone, Copy)]
+pub enum Event {
+    Alloc { id: usize, size: Word },
+    Free { id: usize },
+}
+
+/// an event stream that frees everything it allocates, so it can be replayed
+/// over the same allocator again and again
+pub struct Workload {
+    pub name: String,
+    pub events: Vec<Event>,
+    /// number of distinct ids, i.e. slots needed to replay
+    pub ids: usize,
+}
+
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    fn unit(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// sizes 8..=256, each allocation freed after a random number of later ones
+pub fn uniform_small(n: usize) -> Workload {
+    let mut rng = XorShift(0x5eed_0001);
+    random_lifetimes_with("uniform_small", n, 64, |_| 8 + rng.below(249) as Word)
+}
+
+/// Pareto distributed sizes from 16 bytes up to 64 KiB: mostly small
+/// allocations with a long tail of large ones
+pub fn power_law(n: usize) -> Workload {
+    let mut rng = XorShift(0x5eed_0002);
+    random_lifetimes_with("power_law", n, 64, |_| {
+        let size = 16.0 / (1.0 - rng.unit()).powf(1.0 / 1.2);
+        size.min(65536.0) as Word
+    })
+}
+
+/// allocations freed in the order they were made, with `window` live at once
+pub fn fifo(n: usize, window: usize) -> Workload {
+    let mut rng = XorShift(0x5eed_0003);
+    let mut events = Vec::with_capacity(2 * n);
+    for id in 0..n {
+        events.push(Event::Alloc {
+            id,
+            size: 16 + rng.below(1024) as Word,
+        });
+        if id >= window {
+            events.push(Event::Free { id: id - window });
+        }
+    }
+    events.extend((n.saturating_sub(window)..n).map(|id| Event::Free { id }));
+    Workload {
+        name: "fifo".into(),
+        events,
+        ids: n,
+    }
+}
+
+/// stack discipline: runs of allocations freed newest first
+pub fn lifo(n: usize, depth: usize) -> Workload {
+    let mut rng = XorShift(0x5eed_0004);
+    let mut events = Vec::with_capacity(2 * n);
+    let mut stack = Vec::new();
+    for id in 0..n {
+        events.push(Event::Alloc {
+            id,
+            size: 16 + rng.below(1024) as Word,
+        });
+        stack.push(id);
+        if stack.len() == depth || rng.below(4) == 0 {
+            let pop = 1 + rng.below(stack.len() as u64) as usize;
+            for _ in 0..pop {
+                events.push(Event::Free {
+                    id: stack.pop().unwrap(),
+                });
+            }
+        }
+    }
+    events.extend(stack.into_iter().rev().map(|id| Event::Free { id }));
+    Workload {
+        name: "lifo".into(),
+        events,
+        ids: n,
+    }
+}
+
+/// sizes 16..=4096, lifetimes exponentially distributed with a mean of
+/// `mean_live` allocations
+pub fn random_lifetimes(n: usize, mean_live: usize) -> Workload {
+    let mut rng = XorShift(0x5eed_0005);
+    random_lifetimes_with("random_lifetimes", n, mean_live, |_| {
+        16 + rng.below(4081) as Word
+    })
+}
+
+fn random_lifetimes_with(
+    name: &str,
+    n: usize,
+    mean_live: usize,
+    mut size: impl FnMut(usize) -> Word,
+) -> Workload {
+    let mut rng = XorShift(0x5eed_1000 ^ n as u64);
+    // (free at step, id)
+    let mut deaths = BinaryHeap::new();
+    let mut events = Vec::with_capacity(2 * n);
+    for id in 0..n {
+        while let Some(&Reverse((at, dead))) = deaths.peek() {
+            if at > id {
+                break;
+            }
+            deaths.pop();
+            events.push(Event::Free { id: dead });
+        }
+        events.push(Event::Alloc { id, size: size(id) });
+        let life = -(mean_live as f64) * (1.0 - rng.unit()).ln();
+        deaths.push(Reverse((id + 1 + life as usize, id)));
+    }
+    events.extend(std::iter::from_fn(|| deaths.pop()).map(|Reverse((_, id))| Event::Free { id }));
+    Workload {
+        name: name.into(),
+        events,
+        ids: n,
+    }
+}
+
+/// Parses a trace of `a <id> <size>` and `f <id>` lines, sizes non zero; blank lines and
+/// lines starting with `#` are skipped. Ids may be reused once freed. Live
+/// allocations left at the end are freed.
+pub fn trace(name: &str, text: &str) -> Workload {
+    let mut slots = HashMap::new();
+    let mut live = BTreeSet::new();
+    let mut events = Vec::new();
+    let mut ids = 0;
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<_> = line.split_whitespace().collect();
+        let event = match fields[..] {
+            ["a", key, size] => size.parse().ok().filter(|&size| size > 0).map(|size| {
+                slots.insert(key.to_string(), ids);
+                live.insert(ids);
+                ids += 1;
+                Event::Alloc { id: ids - 1, size }
+            }),
+            ["f", key] => slots.remove(key).map(|id| {
+                live.remove(&id);
+                Event::Free { id }
+            }),
+            _ => None,
+        };
+        events.push(event.unwrap_or_else(|| panic!("{name}:{}: bad trace line {line:?}", i + 1)));
+    }
+    events.extend(live.into_iter().map(|id| Event::Free { id }));
+    Workload {
+        name: format!("trace_{name}"),
+        events,
+        ids,
+    }
+}
+
+pub trait BenchAlloc {
+    type Handle: Copy;
+
+    fn alloc(&mut self, size: Word) -> Option<Self::Handle>;
+    fn free(&mut self, handle: Self::Handle);
+    /// largest free extent and total free bytes, if the allocator knows them
+    fn free_extents(&self) -> Option<(u64, u64)>;
+}
+
+impl BenchAlloc for SubAllocator {
+    type Handle = Word;
+
+    fn alloc(&mut self, size: Word) -> Option<Word> {
+        self.allocate(size).ok()
+    }
+
+    fn free(&mut self, addr: Word) {
+        self.deallocate(addr).unwrap();
+    }
+
+    fn free_extents(&self) -> Option<(u64, u64)> {
+        let stats = self.stats();
+        Some((stats.largest_free as u64, stats.free as u64))
+    }
+}
+
+/// the process allocator, through `GlobalAlloc` with 8 byte alignment
+pub struct SystemAlloc;
+
+impl BenchAlloc for SystemAlloc {
+    type Handle = (NonNull<u8>, Word);
+
+    fn alloc(&mut self, size: Word) -> Option<Self::Handle> {
+        let layout = Layout::from_size_align(size as usize, 8).ok()?;
+        NonNull::new(unsafe { System.alloc(layout) }).map(|ptr| (ptr, size))
+    }
+
+    fn free(&mut self, (ptr, size): Self::Handle) {
+        let layout = Layout::from_size_align(size as usize, 8).unwrap();
+        unsafe { System.dealloc(ptr.as_ptr(), layout) };
+    }
+
+    fn free_extents(&self) -> Option<(u64, u64)> {
+        None
+    }
+}
+
+/// Baseline: a single address ordered free list searched first fit, with
+/// coalescing on free. Only tracks ranges, it owns no memory.
+pub struct FirstFit {
+    /// (start, len), sorted by start, never adjacent
+    free: Vec<(Word, Word)>,
+}
+
+impl FirstFit {
+    pub fn new(capacity: Word) -> Self {
+        Self {
+            free: vec![(0, capacity)],
+        }
+    }
+}
+
+impl BenchAlloc for FirstFit {
+    type Handle = (Word, Word);
+
+    fn alloc(&mut self, size: Word) -> Option<Self::Handle> {
+        let size = size.next_multiple_of(8);
+        let i = self.free.iter().position(|&(_, len)| len >= size)?;
+        let (start, len) = self.free[i];
+        match len == size {
+            true => {
+                self.free.remove(i);
+            }
+            false => self.free[i] = (start + size, len - size),
+        }
+        Some((start, size))
+    }
+
+    fn free(&mut self, (start, size): Self::Handle) {
+        let i = self.free.partition_point(|&(s, _)| s < start);
+        let merges_prev = i > 0 && {
+            let (s, len) = self.free[i - 1];
+            s + len == start
+        };
+        let merges_next = i < self.free.len() && start + size == self.free[i].0;
+        match (merges_prev, merges_next) {
+            (true, true) => {
+                self.free[i - 1].1 += size + self.free[i].1;
+                self.free.remove(i);
+            }
+            (true, false) => self.free[i - 1].1 += size,
+            (false, true) => self.free[i] = (start, size + self.free[i].1),
+            (false, false) => self.free.insert(i, (start, size)),
+        }
+    }
+
+    fn free_extents(&self) -> Option<(u64, u64)> {
+        let largest = self.free.iter().map(|&(_, len)| len as u64).max();
+        let total = self.free.iter().map(|&(_, len)| len as u64).sum();
+        Some((largest.unwrap_or(0), total))
+    }
+}
+
+/// replays `workload`, returning the number of failed allocations
+pub fn replay<A: BenchAlloc>(
+    a: &mut A,
+    workload: &Workload,
+    slots: &mut [Option<A::Handle>],
+) -> usize {
+    let mut failed = 0;
+    for &event in &workload.events {
+        match event {
+            Event::Alloc { id, size } => {
+                slots[id] = a.alloc(size);
+                failed += slots[id].is_none() as usize;
+            }
+            Event::Free { id } => {
+                if let Some(handle) = slots[id].take() {
+                    a.free(handle);
+                }
+            }
+        }
+    }
+    failed
+}
+
+#[derive(Default)]
+pub struct Profile {
+    /// nanoseconds per call
+    pub alloc_ns: Vec<u64>,
+    pub free_ns: Vec<u64>,
+    /// `1 - largest free extent / free bytes`, sampled after every allocation
+    pub fragmentation: Vec<f64>,
+    pub failed: usize,
+}
+
+/// replays `workload` once, timing every call
+pub fn profile<A: BenchAlloc>(a: &mut A, workload: &Workload) -> Profile {
+    let mut slots = vec![None; workload.ids];
+    let mut profile = Profile::default();
+    for &event in &workload.events {
+        match event {
+            Event::Alloc { id, size } => {
+                let start = Instant::now();
+                slots[id] = a.alloc(size);
+                profile.alloc_ns.push(start.elapsed().as_nanos() as u64);
+                profile.failed += slots[id].is_none() as usize;
+                if let Some((largest, total)) = a.free_extents()
+                    && total > 0
+                {
+                    profile
+                        .fragmentation
+                        .push(1.0 - largest as f64 / total as f64);
+                }
+            }
+            Event::Free { id } => {
+                if let Some(handle) = slots[id].take() {
+                    let start = Instant::now();
+                    a.free(handle);
+                    profile.free_ns.push(start.elapsed().as_nanos() as u64);
+                }
+            }
+        }
+    }
+    profile
+}
@@ -0,0 +1,94 @@
+//! Interprets the input as a heap size followed by an operation stream and
+//! checks the heap after every step.
+//!
+//! Input layout: two bytes (LE) heap size in 8 byte words, then per operation
+//! one opcode byte whose low two bits select
+//! - `0` allocate, `1` allocate at the top (`AllocHint::High`), both followed
+//!   by a two byte (LE) size, zero read as one
+//! - `2` deallocate, followed by one byte indexing the live allocations
+//! - `3` reset
+//!
+//! Run with `cargo fuzz run ops corpus/ops seeds/ops` from this directory; the
+//! hand written edge cases live in `seeds/ops` and new finds go to `corpus`.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use suballoc::{AllocHint, Storage, SubAllocator, Word};
+
+const MAX_WORDS: Word = 8192;
+
+struct Input<'a>(&'a [u8]);
+
+impl Input<'_> {
+    fn byte(&mut self) -> Option<u8> {
+        let (&b, rest) = self.0.split_first()?;
+        self.0 = rest;
+        Some(b)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_le_bytes([self.byte()?, self.byte()?]))
+    }
+}
+
+fn fill_of(addr: Word) -> u8 {
+    (addr >> 3) as u8 | 1
+}
+
+fn check(sa: &SubAllocator, live: &[Word]) {
+    sa.check_integrity().unwrap();
+    assert_eq!(sa.used_blocks().count(), live.len());
+    for &addr in live {
+        let payload = sa.payload(addr).unwrap();
+        assert!(payload.iter().all(|&b| b == fill_of(addr)), "{addr} clobbered");
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    let mut input = Input(data);
+    let Some(words) = input.u16() else {
+        return;
+    };
+    let mem_len = (words as Word % MAX_WORDS + 3) * 8;
+    let mut sa = SubAllocator::new(mem_len);
+    let mut live = Vec::new();
+
+    while let Some(op) = input.byte() {
+        match op & 3 {
+            kind @ (0 | 1) => {
+                let Some(size) = input.u16() else { break };
+                let hint = match kind {
+                    0 => AllocHint::Low,
+                    _ => AllocHint::High,
+                };
+                if let Ok(addr) = sa.allocate_with_hint(size.max(1) as Word, hint) {
+                    let mem = sa.storage().mem_ptr() as *const u8;
+                    let payload = sa.payload_mut(addr).unwrap();
+                    assert!(payload.len() >= size as usize);
+                    let range = payload.as_ptr_range();
+                    assert!(mem <= range.start && range.end <= mem.wrapping_add(mem_len as usize));
+                    payload.fill(fill_of(addr));
+                    live.push(addr);
+                }
+            }
+            2 => {
+                let Some(i) = input.byte() else { break };
+                if !live.is_empty() {
+                    let addr = live.swap_remove(i as usize % live.len());
+                    sa.deallocate(addr).unwrap();
+                }
+            }
+            _ => {
+                sa.reset();
+                live.clear();
+            }
+        }
+        check(&sa, &live);
+    }
+
+    for addr in live {
+        sa.deallocate(addr).unwrap();
+    }
+    assert_eq!(sa.free(), sa.capacity());
+});
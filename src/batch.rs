@@ -0,0 +1,142 @@
+use crate::block::{BLOCK_ALIGNMENT, BitFlags, BlockHead, BlockHeadPtrInterface, BlockInterface};
+use crate::guard::GUARD_SIZE;
+use crate::meta::{align_up, byte_add_into, strip_meta, with_meta};
+use crate::observer::AllocObserver;
+use crate::storage::Storage;
+use crate::tlsf::{AllocError, AllocResult, SubAllocator, Word};
+
+impl<S: Storage, O: AllocObserver> SubAllocator<S, O> {
+    /// allocates `count` blocks of `size` bytes into `out[..count]`, carved back
+    /// to back from a single free block when one is large enough. Either all
+    /// blocks are allocated or none.
+    #[cfg_attr(feature = "alloc-sites", track_caller)]
+    pub fn allocate_many(&mut self, size: Word, count: usize, out: &mut [Word]) -> AllocResult<()> {
+        if size == 0 || out.len() < count {
+            return Err(AllocError::InvalidAllocation);
+        }
+        let out = &mut out[..count];
+        if count == 0 {
+            return Ok(());
+        }
+        match self.allocate_run(size, out) {
+            Err(AllocError::OutOfMemory) => self.allocate_each(size, out)?,
+            res => res?,
+        }
+        for &addr in out.iter() {
+            self.record_allocation(addr, None, 0);
+        }
+        Ok(())
+    }
+
+    fn allocate_run(&mut self, size: Word, out: &mut [Word]) -> AllocResult<()> {
+        let aligned_size = align_up(size, BLOCK_ALIGNMENT) + GUARD_SIZE;
+        let stride = with_meta(aligned_size);
+        let run_size = (out.len() as Word)
+            .checked_mul(stride)
+            .map(strip_meta)
+            .ok_or(AllocError::OutOfMemory)?;
+
+        let first = self.update(|tlsf| {
+            let (fli, sli) = tlsf.mapping_search(run_size)?;
+            let head_ptr = tlsf.popf_free_link(fli, sli);
+            let first = tlsf.mem_offset_from_ptr(head_ptr);
+            let last_head_ptr = tlsf.carve_run(head_ptr, aligned_size, out.len());
+
+            let end = tlsf.mem_offset_from_ptr(last_head_ptr) + with_meta(last_head_ptr.size());
+            tlsf.ctl_mut().take_pristine(first, end);
+            Ok(first)
+        })?;
+        for (i, addr) in out.iter_mut().enumerate() {
+            *addr = first + i as Word * stride;
+        }
+        for &addr in out.iter() {
+            if let Err(err) = self.arm_guard(addr, size) {
+                self.undo_allocation(first, out[out.len() - 1]);
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    // splits the popped free block into `count` used blocks, the last one
+    // taking the leftover like a single allocation would
+    fn carve_run(
+        &mut self,
+        mut head_ptr: *mut BlockHead,
+        aligned_size: Word,
+        count: usize,
+    ) -> *mut BlockHead {
+        let block_size = head_ptr.size();
+        let stride = with_meta(aligned_size);
+        self.set_prev_next_used(head_ptr);
+
+        let size_flags = aligned_size | BitFlags::USED | BitFlags::PREV_USED | BitFlags::NEXT_USED;
+        for _ in 1..count {
+            head_ptr.set_size_flags(size_flags);
+            head_ptr.tail_ptr(aligned_size).set_size_flags(size_flags);
+            head_ptr = unsafe { byte_add_into(head_ptr, stride as usize) };
+        }
+        let rest = block_size - (count as Word - 1) * stride;
+        head_ptr.set_size_flags(rest | BitFlags::PREV_USED | BitFlags::NEXT_USED);
+        self.set_block_used(head_ptr, aligned_size);
+        head_ptr
+    }
+
+    fn allocate_each(&mut self, size: Word, out: &mut [Word]) -> AllocResult<()> {
+        for i in 0..out.len() {
+            match self.allocate_block(size) {
+                Ok(addr) => out[i] = addr,
+                Err(err) => {
+                    for &addr in &out[..i] {
+                        self.undo_allocation(addr, addr);
+                    }
+                    return Err(err);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// frees all `addrs`, coalescing physically adjacent ones into a single
+    /// free block before touching the bins. An address given twice frees
+    /// nothing.
+    pub fn deallocate_many(&mut self, addrs: &[Word]) -> AllocResult<()> {
+        let mut sorted = addrs.to_vec();
+        sorted.sort_unstable();
+        if sorted.windows(2).any(|pair| pair[0] == pair[1]) {
+            return Err(AllocError::InvalidAllocation);
+        }
+        for &addr in &sorted {
+            self.check_guard(addr)?;
+        }
+        for &addr in &sorted {
+            self.observer.on_deallocate(addr, self.block_size(addr));
+        }
+        if self.scrub_on_free {
+            sorted.iter().for_each(|&addr| self.scrub_payload(addr));
+        }
+
+        self.update(|tlsf| {
+            let mut rest = &sorted[..];
+            while let Some((&first, tail)) = rest.split_first() {
+                let mut last = first;
+                let run = tail
+                    .iter()
+                    .take_while(|&&addr| {
+                        let adjacent = addr == last + with_meta(tlsf.block_size(last));
+                        if adjacent {
+                            last = addr;
+                        }
+                        adjacent
+                    })
+                    .count();
+                tlsf.free_run(first, last);
+                rest = &tail[run..];
+            }
+        });
+        for &addr in &sorted {
+            self.release_record(addr);
+        }
+        Ok(())
+    }
+}
@@ -2,7 +2,7 @@ use crate::meta::{byte_add_into, byte_sub_into, with_head};
 use crate::tlsf::{WORD_BITS, Word};
 
 pub(crate) const BLOCK_ALIGNMENT: Word = 8;
-pub(crate) const BLOCK_HEAD_SIZE: Word = size_of::<UsedBlockHead>() as Word;
+pub(crate) const BLOCK_HEAD_SIZE: Word = size_of::<BlockHead>() as Word;
 pub(crate) const BLOCK_TAIL_SIZE: Word = size_of::<BlockTail>() as Word;
 pub(crate) const BLOCK_META_SIZE: Word = BLOCK_HEAD_SIZE + BLOCK_TAIL_SIZE;
 pub(crate) const PACKED_NONE_PTR: Word = Word::MAX;
@@ -18,126 +18,117 @@ impl BitFlags {
     pub const SIZE_MASK: Word = !0b111;
 }
 
-pub(crate) trait BlockInterface {
+/// Size and flags word shared by block heads and tails. Metadata is only
+/// ever accessed through raw pointers into the heap, never through references,
+/// so neighbouring blocks can be updated while pointers to this one are held.
+pub(crate) trait BlockInterface: Copy {
+    fn word_ptr(self) -> *mut Word;
+
     #[inline(always)]
-    fn size(&self) -> Word {
-        let ptr = self as *const _ as *const Word;
-        unsafe { *ptr & BitFlags::SIZE_MASK }
+    fn size(self) -> Word {
+        unsafe { self.word_ptr().read() & BitFlags::SIZE_MASK }
     }
     #[inline(always)]
-    fn flags(&self) -> Word {
-        let ptr = self as *const _ as *const Word;
-        unsafe { *ptr & !BitFlags::SIZE_MASK }
+    fn flags(self) -> Word {
+        unsafe { self.word_ptr().read() & !BitFlags::SIZE_MASK }
     }
     #[inline(always)]
-    fn set_size_flags(&mut self, word: Word) {
-        let ptr = self as *mut _ as *mut Word;
-        unsafe { *ptr = word }
+    fn set_size_flags(self, word: Word) {
+        unsafe { self.word_ptr().write(word) }
     }
     #[inline(always)]
-    fn or_flags(&mut self, flags: Word) {
-        let ptr = self as *mut _ as *mut Word;
-        unsafe { *ptr |= flags }
+    fn or_flags(self, flags: Word) {
+        unsafe { *self.word_ptr() |= flags }
     }
     #[inline(always)]
-    fn clear_or_flags(&mut self, flags: Word) {
-        let ptr = self as *mut _ as *mut Word;
-        unsafe { *ptr &= !flags }
+    fn clear_or_flags(self, flags: Word) {
+        unsafe { *self.word_ptr() &= !flags }
     }
     #[inline(always)]
-    fn used(&self) -> bool {
-        let ptr = self as *const _ as *const Word;
-        unsafe { (*ptr & BitFlags::USED) != 0 }
+    fn used(self) -> bool {
+        self.flags() & BitFlags::USED != 0
     }
     #[inline(always)]
-    fn next_used(&self) -> bool {
-        let ptr = self as *const _ as *const Word;
-        unsafe { (*ptr & BitFlags::NEXT_USED) != 0 }
+    fn next_used(self) -> bool {
+        self.flags() & BitFlags::NEXT_USED != 0
     }
     #[inline(always)]
-    fn prev_used(&self) -> bool {
-        let ptr = self as *const _ as *const Word;
-        unsafe { (*ptr & BitFlags::PREV_USED) != 0 }
+    fn prev_used(self) -> bool {
+        self.flags() & BitFlags::PREV_USED != 0
     }
 }
 pub(crate) trait BlockTailPtrInterface {
+    fn head_ptr(self, block_size: Word) -> *mut BlockHead;
+}
+impl BlockTailPtrInterface for *mut BlockTail {
     #[inline(always)]
-    fn deref<'a>(&mut self) -> &'a mut BlockTail {
-        unsafe { &mut **(self as *const _ as *const *mut BlockTail) }
+    fn head_ptr(self, block_size: Word) -> *mut BlockHead {
+        unsafe { byte_sub_into(self, with_head(block_size) as _) }
     }
+}
+/// free blocks keep their bin links, packed as offsets from mem start, in the
+/// first payload word
+pub(crate) trait BlockHeadPtrInterface: Copy {
+    fn tail_ptr(self, block_size: Word) -> *mut BlockTail;
+    fn links_ptr(self) -> *mut u64;
+
     #[inline(always)]
-    fn head_ptr(&self, block_size: Word) -> *mut BlockHead {
-        let ptr = unsafe { *(self as *const _ as *const *mut BlockTail) };
-        let head_offset = with_head(block_size) as _;
-        unsafe { byte_sub_into(ptr, head_offset) }
+    fn link_offsets(self) -> (Word, Word) {
+        let links = unsafe { self.links_ptr().read() };
+        ((links >> WORD_BITS) as Word, links as Word)
     }
-}
-pub(crate) trait BlockHeadPtrInterface {
     #[inline(always)]
-    fn deref<'a>(&mut self) -> &'a mut BlockHead {
-        unsafe { &mut **(self as *const _ as *const *mut BlockHead) }
+    fn set_links(self, links: u64) {
+        unsafe { self.links_ptr().write(links) }
     }
     #[inline(always)]
-    fn tail_ptr(&self, block_size: Word) -> *mut BlockTail {
-        let ptr = unsafe { *(self as *const _ as *const *mut BlockHead) };
-        let tail_offset = with_head(block_size) as _;
-        unsafe { byte_add_into(ptr, tail_offset) }
+    fn set_prev_link(self, link: Word) {
+        let links_ptr = self.links_ptr();
+        unsafe { *links_ptr = (*links_ptr & LOW_MASK) | ((link as u64) << WORD_BITS) }
+    }
+    #[inline(always)]
+    fn set_next_link(self, link: Word) {
+        let links_ptr = self.links_ptr();
+        unsafe { *links_ptr = (*links_ptr & HIGH_MASK) | link as u64 }
     }
 }
-
-pub(crate) union BlockHead {
-    pub free: FreeBlockHead,
-    pub used: UsedBlockHead,
-}
-
-impl BlockHead {
-    pub fn as_free(&mut self) -> &mut FreeBlockHead {
-        unsafe { &mut self.free }
+impl BlockHeadPtrInterface for *mut BlockHead {
+    #[inline(always)]
+    fn tail_ptr(self, block_size: Word) -> *mut BlockTail {
+        unsafe { byte_add_into(self, with_head(block_size) as _) }
+    }
+    #[inline(always)]
+    fn links_ptr(self) -> *mut u64 {
+        unsafe { &raw mut (*self.cast::<FreeBlockHead>()).links }
     }
 }
 
 #[repr(C, align(8))]
-#[derive(Debug, Copy, Clone)]
-pub(crate) struct FreeBlockHead {
+pub(crate) struct BlockHead {
     size_and_flags: Word,
-    links: u64, // 4 bytes prev, 4 bytes next, measured as offset from mem start
 }
-impl BlockInterface for BlockHead {}
-impl BlockHeadPtrInterface for *mut BlockHead {}
 
+/// a free block's head together with the first payload word
 #[repr(C, align(8))]
-#[derive(Debug, Copy, Clone)]
-pub(crate) struct UsedBlockHead {
-    size_and_flags: Word,
+struct FreeBlockHead {
+    head: BlockHead,
+    links: u64,
 }
 
 #[repr(C, align(8))]
-#[derive(Debug, Copy, Clone)]
 pub(crate) struct BlockTail {
     size_and_flags: Word,
 }
-impl BlockInterface for BlockTail {}
-impl BlockTailPtrInterface for *mut BlockTail {}
 
-impl FreeBlockHead {
+impl BlockInterface for *mut BlockHead {
     #[inline(always)]
-    pub fn link_offsets(&self) -> (Word, Word) {
-        let next_link = self.links as Word;
-        let prev_link = (self.links >> WORD_BITS) as Word;
-        (prev_link as Word, next_link as Word)
-    }
-    #[inline(always)]
-    pub fn set_links(&mut self, links: u64) {
-        self.links = links;
-    }
-    #[inline(always)]
-    pub fn set_prev_link(&mut self, link: Word) {
-        let links_masked = self.links & LOW_MASK;
-        self.links = links_masked | ((link as u64) << WORD_BITS);
+    fn word_ptr(self) -> *mut Word {
+        unsafe { &raw mut (*self).size_and_flags }
     }
+}
+impl BlockInterface for *mut BlockTail {
     #[inline(always)]
-    pub fn set_next_link(&mut self, link: Word) {
-        let links_masked = self.links & HIGH_MASK;
-        self.links = links_masked | (link as u64);
+    fn word_ptr(self) -> *mut Word {
+        unsafe { &raw mut (*self).size_and_flags }
     }
 }
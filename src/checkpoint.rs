@@ -0,0 +1,93 @@
+use crate::observer::AllocObserver;
+use crate::storage::Storage;
+use crate::tlsf::{AllocError, AllocResult, SubAllocator, Word};
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+/// position in the allocation sequence, see `SubAllocator::checkpoint`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Checkpoint(u64);
+
+#[derive(Default)]
+pub(crate) struct CheckpointLog {
+    next_seq: u64,
+    /// live checkpoints per position; allocations are only logged while any
+    marks: BTreeMap<u64, usize>,
+    by_seq: BTreeMap<u64, Word>,
+    by_addr: BTreeMap<Word, u64>,
+}
+
+impl CheckpointLog {
+    pub fn on_allocate(&mut self, addr: Word) {
+        if self.marks.is_empty() {
+            return;
+        }
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.by_seq.insert(seq, addr);
+        self.by_addr.insert(addr, seq);
+    }
+
+    pub fn on_deallocate(&mut self, addr: Word) {
+        if let Some(seq) = self.by_addr.remove(&addr) {
+            self.by_seq.remove(&seq);
+        }
+    }
+
+    /// drops every checkpoint and logged allocation
+    pub fn clear(&mut self) {
+        self.marks.clear();
+        self.by_seq.clear();
+        self.by_addr.clear();
+    }
+
+    fn release(&mut self, mark: Checkpoint) -> AllocResult<()> {
+        let count = self
+            .marks
+            .get_mut(&mark.0)
+            .ok_or(AllocError::InvalidAllocation)?;
+        *count -= 1;
+        if *count == 0 {
+            self.marks.remove(&mark.0);
+        }
+        // allocations older than every remaining mark can no longer be rolled back
+        let oldest = self.marks.keys().next().copied().unwrap_or(self.next_seq);
+        let kept = self.by_seq.split_off(&oldest);
+        for addr in core::mem::replace(&mut self.by_seq, kept).into_values() {
+            self.by_addr.remove(&addr);
+        }
+        Ok(())
+    }
+}
+
+impl<S: Storage, O: AllocObserver> SubAllocator<S, O> {
+    /// marks the current point for `rollback`. Allocations are logged while
+    /// any checkpoint is live, until each is released with `commit`.
+    pub fn checkpoint(&mut self) -> Checkpoint {
+        let log = &mut self.checkpoints;
+        *log.marks.entry(log.next_seq).or_default() += 1;
+        Checkpoint(log.next_seq)
+    }
+
+    /// Frees every allocation made since `mark` and still live, in any order.
+    /// Frees since `mark` are not undone. `mark` stays live and may be rolled
+    /// back to again.
+    pub fn rollback(&mut self, mark: Checkpoint) -> AllocResult<()> {
+        if !self.checkpoints.marks.contains_key(&mark.0) {
+            return Err(AllocError::InvalidAllocation);
+        }
+        let since = self
+            .checkpoints
+            .by_seq
+            .range(mark.0..)
+            .map(|(_, &addr)| addr)
+            .collect::<Vec<_>>();
+        self.deallocate_many(&since)
+    }
+
+    /// keeps the allocations made since `mark` and releases it; once no
+    /// checkpoint is live allocations are no longer logged
+    pub fn commit(&mut self, mark: Checkpoint) -> AllocResult<()> {
+        self.checkpoints.release(mark)
+    }
+}
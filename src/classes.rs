@@ -0,0 +1,158 @@
+use crate::stats::Stats;
+use crate::storage::{Control, Storage, valid_mem_len};
+use crate::tlsf::{AllocError, AllocResult, SubAllocator, Word};
+use alloc::{boxed::Box, vec, vec::Vec};
+use core::ptr::NonNull;
+
+/// one class's slice of the `LifetimeHeaps` buffer, with its own bins
+pub struct Partition {
+    mem: NonNull<u8>,
+    len: Word,
+    ctl: NonNull<Control>,
+}
+
+unsafe impl Storage for Partition {
+    fn mem_ptr(&self) -> *mut u8 {
+        self.mem.as_ptr()
+    }
+
+    fn mem_len(&self) -> Word {
+        self.len
+    }
+
+    fn control_ptr(&self) -> *mut Control {
+        self.ctl.as_ptr()
+    }
+
+    fn zeroed(&self) -> bool {
+        true
+    }
+}
+
+impl Drop for Partition {
+    fn drop(&mut self) {
+        unsafe { drop(Box::from_raw(self.ctl.as_ptr())) };
+    }
+}
+
+/// Lifetime classes (e.g. frame, level, permanent) as N independent heaps,
+/// one fixed partition of a shared buffer each, not classes within one
+/// `SubAllocator`. Each partition has its own bins, so transient blocks never
+/// fragment the space of long lived ones, but a full class cannot borrow
+/// from another. Offsets are global across the whole buffer.
+///
+/// The partitions point into the buffer, so their allocators stay borrowed
+/// from it and cannot be moved out:
+///
+/// ```compile_fail
+/// # use suballoc::LifetimeHeaps;
+/// let mut a = LifetimeHeaps::new(&[1024]);
+/// let mut b = LifetimeHeaps::new(&[1024]);
+/// core::mem::swap(a.heap_mut(0).unwrap(), b.heap_mut(0).unwrap());
+/// ```
+pub struct LifetimeHeaps {
+    heaps: Vec<SubAllocator<Partition>>,
+    bases: Vec<Word>,
+    mem: NonNull<[u64]>,
+}
+
+impl LifetimeHeaps {
+    /// one partition of `capacities[class]` bytes per class, each a multiple
+    /// of 8 with room for a free block
+    pub fn new(capacities: &[Word]) -> Self {
+        assert!(!capacities.is_empty());
+        let mut bases = Vec::with_capacity(capacities.len());
+        let mut total: Word = 0;
+        for &capacity in capacities {
+            assert!(valid_mem_len(capacity), "partition too small or unaligned");
+            bases.push(total);
+            total = total.checked_add(capacity).expect("partitions exceed Word");
+        }
+
+        let mem = vec![0u64; total as usize / size_of::<u64>()].into_boxed_slice();
+        let mem = NonNull::from(Box::leak(mem));
+        let heaps = bases
+            .iter()
+            .zip(capacities)
+            .map(|(&base, &len)| {
+                SubAllocator::with_storage(Partition {
+                    mem: unsafe { mem.cast::<u8>().add(base as usize) },
+                    len,
+                    ctl: NonNull::from(Box::leak(Box::new(Control::EMPTY))),
+                })
+            })
+            .collect();
+        Self { heaps, bases, mem }
+    }
+
+    pub fn classes(&self) -> usize {
+        self.heaps.len()
+    }
+
+    #[cfg_attr(feature = "alloc-sites", track_caller)]
+    pub fn allocate(&mut self, class: usize, size: Word) -> AllocResult<Word> {
+        let addr = self.class_heap_mut(class)?.allocate(size)?;
+        Ok(self.bases[class] + addr)
+    }
+
+    pub fn deallocate(&mut self, addr: Word) -> AllocResult<()> {
+        let (class, local) = self.locate(addr)?;
+        self.heaps[class].deallocate(local)
+    }
+
+    pub fn payload(&self, addr: Word) -> AllocResult<&[u8]> {
+        let (class, local) = self.locate(addr)?;
+        self.heaps[class].payload(local)
+    }
+
+    pub fn payload_mut(&mut self, addr: Word) -> AllocResult<&mut [u8]> {
+        let (class, local) = self.locate(addr)?;
+        self.heaps[class].payload_mut(local)
+    }
+
+    pub fn class_of(&self, addr: Word) -> Option<usize> {
+        self.locate(addr).ok().map(|(class, _)| class)
+    }
+
+    pub fn stats(&self, class: usize) -> AllocResult<Stats> {
+        Ok(self.class_heap(class)?.stats())
+    }
+
+    /// frees every allocation of `class`, leaving the other classes untouched
+    pub fn reset(&mut self, class: usize) -> AllocResult<()> {
+        self.class_heap_mut(class)?.reset();
+        Ok(())
+    }
+
+    /// the class's own allocator, its offsets are relative to the partition
+    pub fn heap(&self, class: usize) -> Option<&SubAllocator<Partition>> {
+        self.heaps.get(class)
+    }
+
+    fn class_heap(&self, class: usize) -> AllocResult<&SubAllocator<Partition>> {
+        self.heap(class).ok_or(AllocError::InvalidAllocation)
+    }
+
+    fn class_heap_mut(&mut self, class: usize) -> AllocResult<&mut SubAllocator<Partition>> {
+        self.heaps
+            .get_mut(class)
+            .ok_or(AllocError::InvalidAllocation)
+    }
+
+    fn locate(&self, addr: Word) -> AllocResult<(usize, Word)> {
+        let class = self
+            .bases
+            .partition_point(|&base| base <= addr)
+            .checked_sub(1)
+            .filter(|&class| addr - self.bases[class] < self.heaps[class].storage().mem_len())
+            .ok_or(AllocError::InvalidAllocation)?;
+        Ok((class, addr - self.bases[class]))
+    }
+}
+
+impl Drop for LifetimeHeaps {
+    fn drop(&mut self) {
+        self.heaps.clear();
+        unsafe { drop(Box::from_raw(self.mem.as_ptr())) };
+    }
+}
@@ -0,0 +1,69 @@
+use crate::block::{BLOCK_ALIGNMENT, BLOCK_HEAD_SIZE, BlockHead};
+use crate::guard::GUARD_SIZE;
+use crate::meta::{align_up, with_meta};
+use crate::observer::AllocObserver;
+use crate::storage::Storage;
+use crate::tlsf::{AllocError, AllocResult, LEFTOVER_ABSORB_SIZE, SubAllocator, Word};
+
+impl<S: Storage, O: AllocObserver> SubAllocator<S, O> {
+    /// allocates the block with its head at `offset`. Fails with `RangeInUse`
+    /// if the block would overlap a used one, and with `InvalidAllocation` if
+    /// `size` is 0, `offset` is unaligned, out of bounds, or leaves a free gap
+    /// in front too small to hold a free block.
+    #[cfg_attr(feature = "alloc-sites", track_caller)]
+    pub fn allocate_at(&mut self, offset: Word, size: Word) -> AllocResult<Word> {
+        if size == 0 {
+            return Err(AllocError::InvalidAllocation);
+        }
+        let aligned_size = align_up(size, BLOCK_ALIGNMENT) + GUARD_SIZE;
+        let end = offset
+            .checked_add(with_meta(aligned_size))
+            .filter(|&end| end <= self.mem_len() && offset.is_multiple_of(BLOCK_ALIGNMENT))
+            .ok_or(AllocError::InvalidAllocation)?;
+
+        let free = self
+            .blocks()
+            .find(|b| b.end() > offset)
+            .ok_or(AllocError::InvalidAllocation)?;
+        let conflict = match free.used {
+            true => Some(free),
+            false => self
+                .blocks()
+                .find(|b| b.offset >= free.end() && b.offset < end),
+        };
+        if let Some(used) = conflict {
+            return Err(AllocError::RangeInUse {
+                offset: used.offset,
+                size: used.size,
+            });
+        }
+        let front = offset - free.offset;
+        if front != 0 && front <= LEFTOVER_ABSORB_SIZE {
+            return Err(AllocError::InvalidAllocation);
+        }
+
+        self.update(|tlsf| {
+            let free_head_ptr: *mut BlockHead = tlsf.ptr_from_mem_offset_unchecked(free.offset);
+            let (fli, sli) = tlsf.mapping_insert(free.size);
+            tlsf.remove_free_link(fli, sli, free_head_ptr);
+            let block_end = tlsf.carve_block(free.offset..free.end(), offset, aligned_size);
+            tlsf.ctl_mut().take_pristine(offset, block_end);
+        });
+        if let Err(err) = self.arm_guard(offset, size) {
+            self.undo_allocation(offset, offset);
+            return Err(err);
+        }
+        self.record_allocation(offset, None, 0);
+        Ok(offset)
+    }
+
+    /// allocates a block whose payload starts at `start`, so that mem bytes
+    /// `start..start + size` belong to it
+    #[cfg_attr(feature = "alloc-sites", track_caller)]
+    pub fn reserve_range(&mut self, start: Word, size: Word) -> AllocResult<Word> {
+        let offset = start
+            .checked_sub(BLOCK_HEAD_SIZE)
+            .ok_or(AllocError::InvalidAllocation)?;
+        self.allocate_at(offset, size)
+    }
+}
@@ -0,0 +1,146 @@
+//! Debug guards: freed payloads are filled with `POISON` and checked when the
+//! block is handed out again, and every allocation is followed by at least
+//! `GUARD_SIZE` bytes of `CANARY`, checked in `deallocate`. The requested size
+//! lives in the otherwise unused upper half of the block head, out of reach of
+//! the allocation's own overruns.
+
+use crate::observer::AllocObserver;
+use crate::storage::Storage;
+use crate::tlsf::{AllocResult, SubAllocator, Word};
+#[cfg(feature = "guards")]
+use crate::{
+    block::{BLOCK_HEAD_SIZE, BlockHead, BlockInterface},
+    meta::{with_head, with_meta},
+    tlsf::AllocError,
+};
+
+#[cfg(feature = "guards")]
+pub(crate) const GUARD_SIZE: Word = 8;
+#[cfg(not(feature = "guards"))]
+pub(crate) const GUARD_SIZE: Word = 0;
+
+#[cfg(feature = "guards")]
+pub const POISON: u8 = 0xdd;
+#[cfg(feature = "guards")]
+pub const CANARY: u8 = 0xca;
+
+// the first payload word of a free block holds its links
+#[cfg(feature = "guards")]
+const LINKS_SIZE: Word = 8;
+#[cfg(feature = "guards")]
+const REQUESTED_SIZE_AT: usize = BLOCK_HEAD_SIZE as usize / 2;
+
+#[cfg(feature = "guards")]
+impl<S: Storage, O: AllocObserver> SubAllocator<S, O> {
+    fn poison_payload(&mut self, offset: Word, size: Word) {
+        let start = (with_head(offset) + LINKS_SIZE) as usize;
+        let end = (with_head(offset) + size) as usize;
+        self.mem_bytes_mut()[start..end].fill(POISON);
+    }
+
+    pub(crate) fn poison_free_block(&mut self, head_ptr: *mut BlockHead) {
+        let size = head_ptr.size();
+        self.poison_payload(self.mem_offset_from_ptr(head_ptr), size);
+    }
+
+    /// paints every free block, the heap is no longer pristine afterwards
+    pub(crate) fn poison_free_blocks(&mut self) {
+        let mut offset = 0;
+        while offset < self.mem_len() {
+            let head_ptr: *mut BlockHead = self.ptr_from_mem_offset_unchecked(offset);
+            let size = head_ptr.size();
+            if !head_ptr.used() {
+                self.poison_payload(offset, size);
+            }
+            offset += with_meta(size);
+        }
+        self.ctl_mut().set_pristine(0..0);
+    }
+
+    pub(crate) fn arm_guard(&mut self, addr: Word, size: Word) -> AllocResult<()> {
+        let block_size = self.block_size(addr);
+        let start = with_head(addr) as usize;
+        let payload = &mut self.mem_bytes_mut()[start..start + block_size as usize];
+        if let Some(i) = payload[LINKS_SIZE as usize..]
+            .iter()
+            .position(|&b| b != POISON)
+        {
+            return Err(AllocError::UseAfterFree {
+                offset: addr,
+                size: block_size,
+                at: start as Word + LINKS_SIZE + i as Word,
+            });
+        }
+        self.rearm_guard(addr, size);
+        Ok(())
+    }
+
+    /// writes the canary behind `size` bytes of payload and records `size`
+    /// as requested, without checking the block was poisoned
+    pub(crate) fn rearm_guard(&mut self, addr: Word, size: Word) {
+        let block_size = self.block_size(addr);
+        let start = with_head(addr) as usize;
+        self.mem_bytes_mut()[start + size as usize..start + block_size as usize].fill(CANARY);
+
+        let at = addr as usize + REQUESTED_SIZE_AT;
+        self.mem_bytes_mut()[at..at + size_of::<Word>()].copy_from_slice(&size.to_ne_bytes());
+    }
+
+    pub(crate) fn requested_size(&self, addr: Word, block_size: Word) -> Word {
+        let at = addr as usize + REQUESTED_SIZE_AT;
+        let bytes = self.mem_bytes()[at..at + size_of::<Word>()]
+            .try_into()
+            .unwrap();
+        Word::from_ne_bytes(bytes).min(block_size)
+    }
+
+    pub(crate) fn check_guard(&self, addr: Word) -> AllocResult<()> {
+        let payload = self.payload_range(addr)?;
+        let block_end = payload.start + self.block_size(addr) as usize;
+        match self.mem_bytes()[payload.end..block_end]
+            .iter()
+            .position(|&b| b != CANARY)
+        {
+            Some(i) => Err(AllocError::Overrun {
+                offset: addr,
+                size: payload.len() as Word,
+                at: (payload.end + i) as Word,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// checks the canaries of every used block
+    pub fn check_guards(&self) -> AllocResult<()> {
+        self.used_blocks()
+            .try_for_each(|block| self.check_guard(block.offset))
+    }
+}
+
+#[cfg(not(feature = "guards"))]
+impl<S: Storage, O: AllocObserver> SubAllocator<S, O> {
+    #[inline(always)]
+    pub(crate) fn poison_free_block<T>(&mut self, _head_ptr: *mut T) {}
+
+    #[inline(always)]
+    pub(crate) fn poison_free_blocks(&mut self) {}
+
+    #[inline(always)]
+    pub(crate) fn arm_guard(&mut self, _addr: Word, _size: Word) -> AllocResult<()> {
+        Ok(())
+    }
+
+    #[cfg(feature = "alloc")]
+    #[inline(always)]
+    pub(crate) fn rearm_guard(&mut self, _addr: Word, _size: Word) {}
+
+    #[inline(always)]
+    pub(crate) fn requested_size(&self, _addr: Word, block_size: Word) -> Word {
+        block_size
+    }
+
+    #[inline(always)]
+    pub(crate) fn check_guard(&self, _addr: Word) -> AllocResult<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,23 @@
+use crate::observer::AllocObserver;
+use crate::storage::Storage;
+use crate::tlsf::{AllocResult, SubAllocator, Word};
+
+/// which end of the chosen free block the allocation is placed at
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AllocHint {
+    /// take the front and leave the leftover behind, like `allocate`
+    #[default]
+    Low,
+    /// take the back and leave the leftover in front, so long lived data can
+    /// cluster at the top of the heap away from short lived data
+    High,
+}
+
+impl<S: Storage, O: AllocObserver> SubAllocator<S, O> {
+    #[cfg_attr(feature = "alloc-sites", track_caller)]
+    pub fn allocate_with_hint(&mut self, size: Word, hint: AllocHint) -> AllocResult<Word> {
+        let (addr, _) = self.allocate_fresh_block(size, hint)?;
+        self.record_allocation(addr, None, 0);
+        Ok(addr)
+    }
+}
@@ -0,0 +1,361 @@
+use crate::block::{
+    BLOCK_HEAD_SIZE, BitFlags, BlockHead, BlockHeadPtrInterface, BlockInterface, PACKED_NONE_PTR,
+};
+use crate::guard::GUARD_SIZE;
+use crate::meta::with_meta;
+use crate::observer::AllocObserver;
+use crate::storage::{Control, OwnedStorage, Storage, valid_mem_len};
+use crate::tlsf::{SLI_SIZE, SubAllocator, WORD_BITS, Word};
+use crate::verify::IntegrityError;
+use alloc::vec::Vec;
+use core::fmt::{self, Display};
+
+pub const IMAGE_MAGIC: [u8; 8] = *b"SUBALLOC";
+pub const IMAGE_VERSION: u16 = 1;
+
+const KIND_FULL: u8 = 0;
+const KIND_METADATA: u8 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestoreError {
+    BadMagic,
+    UnsupportedVersion(u16),
+    WordWidth(u8),
+    SliConfig(u8),
+    UnknownKind(u8),
+    Truncated,
+    TrailingBytes,
+    /// block list or bin heads do not describe a heap of the recorded size
+    Malformed(&'static str),
+    Corrupt(IntegrityError),
+}
+
+impl Display for RestoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RestoreError::BadMagic => f.write_str("not a heap image"),
+            RestoreError::UnsupportedVersion(v) => write!(f, "unsupported image version {v}"),
+            RestoreError::WordWidth(bits) => write!(f, "image built for {bits} bit words"),
+            RestoreError::SliConfig(sli) => write!(f, "image built for {sli} second level bins"),
+            RestoreError::UnknownKind(kind) => write!(f, "unknown image kind {kind}"),
+            RestoreError::Truncated => f.write_str("image truncated"),
+            RestoreError::TrailingBytes => f.write_str("trailing bytes after image"),
+            RestoreError::Malformed(reason) => write!(f, "malformed image: {reason}"),
+            RestoreError::Corrupt(err) => Display::fmt(err, f),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RestoreError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RestoreError::Corrupt(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// heap layout without payload bytes: every block's size and flags word plus
+/// the free lists in link order
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HeapMetadata {
+    pub mem_len: Word,
+    pub fl_bitmap: Word,
+    pub sl_bitmaps: [Word; WORD_BITS as usize],
+    pub blocks: Vec<Word>,
+    pub free_bins: Vec<FreeBinImage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FreeBinImage {
+    pub fli: Word,
+    pub sli: Word,
+    pub offsets: Vec<Word>,
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], RestoreError> {
+        if self.buf.len() < n {
+            return Err(RestoreError::Truncated);
+        }
+        let (head, rest) = self.buf.split_at(n);
+        self.buf = rest;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, RestoreError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, RestoreError> {
+        Ok(u16::from_le_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    fn word(&mut self) -> Result<Word, RestoreError> {
+        Ok(Word::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn finish(self) -> Result<(), RestoreError> {
+        match self.buf.is_empty() {
+            true => Ok(()),
+            false => Err(RestoreError::TrailingBytes),
+        }
+    }
+}
+
+fn put_word(out: &mut Vec<u8>, word: Word) {
+    out.extend_from_slice(&word.to_le_bytes());
+}
+
+fn put_header(out: &mut Vec<u8>, kind: u8) {
+    out.extend_from_slice(&IMAGE_MAGIC);
+    out.extend_from_slice(&IMAGE_VERSION.to_le_bytes());
+    out.push(WORD_BITS as u8);
+    out.push(SLI_SIZE as u8);
+    out.push(kind);
+    out.extend_from_slice(&[0; 3]);
+}
+
+fn read_header(r: &mut Reader) -> Result<u8, RestoreError> {
+    if r.bytes(IMAGE_MAGIC.len())? != IMAGE_MAGIC {
+        return Err(RestoreError::BadMagic);
+    }
+    let version = r.u16()?;
+    if version != IMAGE_VERSION {
+        return Err(RestoreError::UnsupportedVersion(version));
+    }
+    let word_bits = r.u8()?;
+    if word_bits as Word != WORD_BITS {
+        return Err(RestoreError::WordWidth(word_bits));
+    }
+    let sli_size = r.u8()?;
+    if sli_size as usize != SLI_SIZE {
+        return Err(RestoreError::SliConfig(sli_size));
+    }
+    let kind = r.u8()?;
+    r.bytes(3)?;
+    Ok(kind)
+}
+
+impl<S: Storage, O: AllocObserver> SubAllocator<S, O> {
+    /// versioned image of the whole heap: bitmaps, bin heads and the mem bytes,
+    /// the latter copied verbatim in native byte order
+    pub fn serialize(&self) -> Vec<u8> {
+        let ctl = self.ctl();
+        let mut out = Vec::with_capacity(self.mem_len() as usize + size_of::<Control>() + 32);
+        put_header(&mut out, KIND_FULL);
+        put_word(&mut out, self.mem_len());
+        put_word(&mut out, ctl.fl_bitmap);
+        ctl.sl_bitmaps.iter().for_each(|&w| put_word(&mut out, w));
+        ctl.free_blocks
+            .iter()
+            .flatten()
+            .for_each(|&w| put_word(&mut out, w));
+        out.extend_from_slice(self.mem_bytes());
+        out
+    }
+
+    /// compact image of `metadata()`, payload bytes are not preserved
+    pub fn serialize_metadata(&self) -> Vec<u8> {
+        let meta = self.metadata();
+        let mut out = Vec::new();
+        put_header(&mut out, KIND_METADATA);
+        put_word(&mut out, meta.mem_len);
+        put_word(&mut out, meta.fl_bitmap);
+        meta.sl_bitmaps.iter().for_each(|&w| put_word(&mut out, w));
+        put_word(&mut out, meta.blocks.len() as Word);
+        meta.blocks.iter().for_each(|&w| put_word(&mut out, w));
+        put_word(&mut out, meta.free_bins.len() as Word);
+        for bin in &meta.free_bins {
+            put_word(&mut out, bin.fli);
+            put_word(&mut out, bin.sli);
+            put_word(&mut out, bin.offsets.len() as Word);
+            bin.offsets.iter().for_each(|&o| put_word(&mut out, o));
+        }
+        out
+    }
+
+    pub fn metadata(&self) -> HeapMetadata {
+        let blocks = self
+            .blocks()
+            .map(|b| {
+                let head_ptr: *mut BlockHead = self.ptr_from_mem_offset_unchecked(b.offset);
+                head_ptr.size() | head_ptr.flags()
+            })
+            .collect();
+        let free_bins = self
+            .free_bins()
+            .map(|(fli, sli, list)| FreeBinImage {
+                fli,
+                sli,
+                offsets: list.collect(),
+            })
+            .collect();
+        HeapMetadata {
+            mem_len: self.mem_len(),
+            fl_bitmap: self.ctl().fl_bitmap,
+            sl_bitmaps: self.ctl().sl_bitmaps,
+            blocks,
+            free_bins,
+        }
+    }
+}
+
+impl SubAllocator {
+    /// rebuilds an allocator from `serialize` or `serialize_metadata` output,
+    /// accepting it only if the heap passes `check_integrity`
+    pub fn restore(image: &[u8]) -> Result<Self, RestoreError> {
+        let mut r = Reader { buf: image };
+        match read_header(&mut r)? {
+            KIND_FULL => Self::restore_full(r),
+            KIND_METADATA => {
+                let meta = Self::read_metadata(&mut r)?;
+                r.finish()?;
+                Self::from_metadata(&meta)
+            }
+            kind => Err(RestoreError::UnknownKind(kind)),
+        }
+    }
+
+    fn restore_full(mut r: Reader) -> Result<Self, RestoreError> {
+        let mem_len = r.word()?;
+        let mut ctl = Control::EMPTY;
+        ctl.fl_bitmap = r.word()?;
+        for w in ctl.sl_bitmaps.iter_mut() {
+            *w = r.word()?;
+        }
+        for bin in ctl.free_blocks.iter_mut().flatten() {
+            *bin = r.word()?;
+        }
+        check_mem_len(mem_len)?;
+        let storage = OwnedStorage::from_bytes(r.bytes(mem_len as usize)?);
+        r.finish()?;
+
+        let mut tlsf = Self::from_formatted_storage(storage);
+        *tlsf.ctl_mut() = ctl;
+        tlsf.ctl_mut().free_bytes = tlsf.count_free_bytes().map_err(RestoreError::Corrupt)?;
+        tlsf.check_integrity().map_err(RestoreError::Corrupt)?;
+        tlsf.poison_free_blocks();
+        Ok(tlsf)
+    }
+
+    fn read_metadata(r: &mut Reader) -> Result<HeapMetadata, RestoreError> {
+        let mem_len = r.word()?;
+        let fl_bitmap = r.word()?;
+        let mut sl_bitmaps = [0; WORD_BITS as usize];
+        for w in sl_bitmaps.iter_mut() {
+            *w = r.word()?;
+        }
+        let block_count = r.word()? as usize;
+        let blocks = (0..block_count.min(r.buf.len() / 4))
+            .map(|_| r.word())
+            .collect::<Result<Vec<_>, _>>()?;
+        if blocks.len() != block_count {
+            return Err(RestoreError::Truncated);
+        }
+        let bin_count = r.word()?;
+        let mut free_bins = Vec::new();
+        for _ in 0..bin_count {
+            let fli = r.word()?;
+            let sli = r.word()?;
+            let len = r.word()? as usize;
+            let offsets = (0..len.min(r.buf.len() / 4))
+                .map(|_| r.word())
+                .collect::<Result<Vec<_>, _>>()?;
+            if offsets.len() != len {
+                return Err(RestoreError::Truncated);
+            }
+            free_bins.push(FreeBinImage { fli, sli, offsets });
+        }
+        Ok(HeapMetadata {
+            mem_len,
+            fl_bitmap,
+            sl_bitmaps,
+            blocks,
+            free_bins,
+        })
+    }
+
+    /// rebuilds a heap with zeroed payloads from its metadata. With `guards`,
+    /// used blocks get their canaries back, as if their whole payload was
+    /// requested.
+    pub fn from_metadata(meta: &HeapMetadata) -> Result<Self, RestoreError> {
+        check_mem_len(meta.mem_len)?;
+        // walk the blocks before allocating a heap of the claimed size
+        let mut offset: Word = 0;
+        for &word in &meta.blocks {
+            let size = word & BitFlags::SIZE_MASK;
+            offset = offset
+                .checked_add(with_meta(size))
+                .filter(|&end| end <= meta.mem_len && size != 0)
+                .ok_or(RestoreError::Malformed("blocks overrun heap"))?;
+        }
+        if offset != meta.mem_len {
+            return Err(RestoreError::Malformed("blocks do not cover heap"));
+        }
+
+        let mut tlsf = Self::from_formatted_storage(OwnedStorage::new(meta.mem_len));
+        let mem = tlsf.mem_bytes_mut();
+        let mut offset: Word = 0;
+        for &word in &meta.blocks {
+            let size = word & BitFlags::SIZE_MASK;
+            let head = offset as usize;
+            let tail = (offset + BLOCK_HEAD_SIZE + size) as usize;
+            mem[head..head + 4].copy_from_slice(&word.to_ne_bytes());
+            mem[tail..tail + 4].copy_from_slice(&word.to_ne_bytes());
+            offset += with_meta(size);
+        }
+
+        tlsf.ctl_mut().fl_bitmap = meta.fl_bitmap;
+        tlsf.ctl_mut().sl_bitmaps = meta.sl_bitmaps;
+        let free_offsets = tlsf.free_blocks().map(|b| b.offset).collect::<Vec<_>>();
+        for bin in &meta.free_bins {
+            if bin.fli >= WORD_BITS || bin.sli as usize >= SLI_SIZE || bin.offsets.is_empty() {
+                return Err(RestoreError::Malformed("invalid free bin"));
+            }
+            tlsf.restore_free_list(bin, &free_offsets)?;
+        }
+        tlsf.ctl_mut().free_bytes = tlsf.count_free_bytes().map_err(RestoreError::Corrupt)?;
+        tlsf.check_integrity().map_err(RestoreError::Corrupt)?;
+        let used = tlsf.used_blocks().collect::<Vec<_>>();
+        for block in used {
+            tlsf.rearm_guard(block.offset, block.size - GUARD_SIZE);
+        }
+        tlsf.poison_free_blocks();
+        Ok(tlsf)
+    }
+
+    fn restore_free_list(
+        &mut self,
+        bin: &FreeBinImage,
+        free_offsets: &[Word],
+    ) -> Result<(), RestoreError> {
+        for (i, &offset) in bin.offsets.iter().enumerate() {
+            if free_offsets.binary_search(&offset).is_err() {
+                return Err(RestoreError::Malformed("bin links a non free block"));
+            }
+            let prev = i.checked_sub(1).map_or(PACKED_NONE_PTR, |p| bin.offsets[p]);
+            let next = bin.offsets.get(i + 1).copied().unwrap_or(PACKED_NONE_PTR);
+            let head_ptr: *mut BlockHead = self.ptr_from_mem_offset_unchecked(offset);
+            head_ptr.set_prev_link(prev);
+            head_ptr.set_next_link(next);
+        }
+        let (fli, sli) = (bin.fli as usize, bin.sli as usize);
+        self.ctl_mut().free_blocks[fli][sli] = bin.offsets[0];
+        Ok(())
+    }
+}
+
+fn check_mem_len(mem_len: Word) -> Result<(), RestoreError> {
+    match valid_mem_len(mem_len) {
+        true => Ok(()),
+        false => Err(RestoreError::Malformed("invalid heap size")),
+    }
+}
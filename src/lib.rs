@@ -1,5 +1,89 @@
+#![no_std]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+#[cfg(feature = "alloc")]
+mod batch;
 mod block;
+#[cfg(feature = "alloc")]
+mod checkpoint;
+#[cfg(feature = "alloc")]
+mod classes;
+mod fixed;
+mod guard;
+mod hint;
+#[cfg(feature = "alloc")]
+mod image;
+#[cfg(any(
+    all(unix, feature = "persistent"),
+    all(target_os = "linux", feature = "shared")
+))]
+mod mapped;
 mod meta;
+mod observer;
+#[cfg(feature = "alloc")]
+mod oom;
+mod ops;
+#[cfg(all(unix, feature = "persistent"))]
+mod persistent;
+#[cfg(feature = "alloc")]
+mod quota;
+#[cfg(feature = "alloc")]
+mod render;
+#[cfg(feature = "alloc")]
+mod ring;
+#[cfg(all(target_os = "linux", feature = "shared"))]
+mod shared;
+mod stats;
+mod storage;
+mod tag;
 mod tlsf;
+#[cfg(feature = "alloc")]
+mod verify;
+#[cfg(feature = "alloc")]
+mod wait;
+mod walk;
+mod zero;
 
-pub use tlsf::{SubAllocator, AllocError, AllocResult, Word};
\ No newline at end of file
+#[cfg(feature = "alloc")]
+pub use checkpoint::Checkpoint;
+#[cfg(feature = "alloc")]
+pub use classes::{LifetimeHeaps, Partition};
+#[cfg(feature = "guards")]
+pub use guard::{CANARY, POISON};
+pub use hint::AllocHint;
+#[cfg(feature = "alloc")]
+pub use image::{FreeBinImage, HeapMetadata, IMAGE_MAGIC, IMAGE_VERSION, RestoreError};
+pub use observer::AllocObserver;
+#[cfg(feature = "tracing")]
+pub use observer::TracingObserver;
+#[cfg(feature = "alloc")]
+pub use oom::{OOM_RETRY_LIMIT, OomAction, OomRequest};
+#[cfg(feature = "op-counts")]
+pub use ops::OpCounts;
+#[cfg(all(unix, feature = "persistent"))]
+pub use persistent::{HEAP_FILE_MAGIC, HEAP_FILE_OFFSET, HEAP_FILE_VERSION, MappedFile};
+#[cfg(feature = "alloc")]
+pub use quota::{QuotaWarning, TagUsage};
+#[cfg(feature = "alloc")]
+pub use render::RenderOptions;
+#[cfg(feature = "alloc")]
+pub use ring::{FreeOrder, RingAllocator, RingFallback};
+#[cfg(all(target_os = "linux", feature = "shared"))]
+pub use shared::{SHARED_REGION_MAGIC, SHARED_REGION_OFFSET, SHARED_REGION_VERSION, SharedRegion};
+pub use stats::Stats;
+#[cfg(feature = "alloc")]
+pub use storage::OwnedStorage;
+pub use storage::{BorrowedStorage, Control, Storage};
+pub use tag::AllocTag;
+#[cfg(feature = "alloc")]
+pub use tag::{LeakEntry, LeakReport};
+pub use tlsf::{AllocError, AllocResult, SubAllocator, Word};
+#[cfg(feature = "alloc")]
+pub use verify::IntegrityError;
+#[cfg(feature = "alloc")]
+pub use wait::{AllocateFuture, AsyncAllocator};
+pub use walk::{BlockInfo, Blocks, FreeBins, FreeList};
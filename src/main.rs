@@ -1,10 +1,9 @@
-mod block;
-mod meta;
-mod tlsf;
+use suballoc::SubAllocator;
+
 fn main() {
-    let mut sa = tlsf::SubAllocator::new(1024);
+    let mut sa = SubAllocator::new(1024);
     let mut alocs = Vec::new();
-    for i in 0..10 {
+    for _ in 0..10 {
         let a = sa.allocate(1).unwrap();
         alocs.push(a);
     }
@@ -13,4 +12,4 @@ fn main() {
         sa.deallocate(i).unwrap();
     }
     dbg!(sa.capacity(), sa.free());
-}
\ No newline at end of file
+}
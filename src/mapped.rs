@@ -0,0 +1,70 @@
+//! Header checks shared by heap files and shared regions, whose headers
+//! start with a `HeaderIdent`.
+
+use crate::storage::valid_mem_len;
+use crate::tlsf::{SLI_SIZE, WORD_BITS, Word};
+use std::format;
+use std::io;
+use std::string::String;
+
+#[repr(C)]
+#[derive(PartialEq, Eq)]
+pub(crate) struct HeaderIdent {
+    magic: [u8; 8],
+    version: u16,
+    word_bits: u8,
+    sli_size: u8,
+}
+
+impl HeaderIdent {
+    /// ident of a heap laid out by this build
+    pub(crate) const fn new(magic: [u8; 8], version: u16) -> Self {
+        Self {
+            magic,
+            version,
+            word_bits: WORD_BITS as u8,
+            sli_size: SLI_SIZE as u8,
+        }
+    }
+
+    /// checks an ident read from a mapping against `expected`
+    pub(crate) fn check(&self, expected: &Self, kind: &str) -> io::Result<()> {
+        if self.magic != expected.magic {
+            return Err(invalid_data(format!("not a {kind}")));
+        }
+        if self.version != expected.version {
+            return Err(invalid_data(format!("unsupported {kind} version")));
+        }
+        if self != expected {
+            return Err(invalid_data(format!(
+                "{kind} built for another word width or SLI config"
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// heap length of a `map_len` byte mapping whose heap starts at `offset`
+pub(crate) fn mapped_mem_len(map_len: u64, offset: usize, kind: &str) -> io::Result<Word> {
+    let mem_len = map_len
+        .checked_sub(offset as u64)
+        .ok_or_else(|| invalid_data(format!("{kind} too small for a heap header")))?;
+    Word::try_from(mem_len).map_err(|_| invalid_data(format!("{kind} too large for Word offsets")))
+}
+
+/// checks the heap length recorded in a header against the mapped one
+pub(crate) fn check_mem_len(recorded: Word, mapped: Word, kind: &str) -> io::Result<()> {
+    if recorded != mapped {
+        return Err(invalid_data(format!(
+            "{kind} length does not match its header"
+        )));
+    }
+    if !valid_mem_len(recorded) {
+        return Err(invalid_data(format!("{kind} heap size is invalid")));
+    }
+    Ok(())
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
@@ -2,37 +2,53 @@ use crate::block::{
     BLOCK_HEAD_SIZE, BLOCK_META_SIZE, BLOCK_TAIL_SIZE, BlockHead, BlockHeadPtrInterface,
     BlockInterface, BlockTail, BlockTailPtrInterface, PACKED_NONE_PTR,
 };
+use crate::observer::AllocObserver;
+use crate::storage::Storage;
 use crate::tlsf::{SubAllocator, Word};
 
-impl SubAllocator {
-    pub(crate) unsafe fn next_block_meta<'a>(
+impl<S: Storage, O: AllocObserver> SubAllocator<S, O> {
+    pub(crate) fn next_block_meta(
         head_ptr: *mut BlockHead,
         block_size: Word,
-    ) -> (&'a mut BlockHead, &'a mut BlockTail) {
-        let mut next_head_ptr: *mut BlockHead =
+    ) -> (*mut BlockHead, *mut BlockTail) {
+        let next_head_ptr: *mut BlockHead =
             unsafe { byte_add_into(head_ptr, with_meta(block_size) as _) };
-        let next_head = next_head_ptr.deref();
-        let next_size = next_head.size();
-        let mut next_tail_ptr = next_head_ptr.tail_ptr(next_size);
-        (next_head_ptr.deref(), next_tail_ptr.deref())
+        let next_size = next_head_ptr.size();
+        let next_tail_ptr = next_head_ptr.tail_ptr(next_size);
+        (next_head_ptr, next_tail_ptr)
     }
 
-    pub(crate) unsafe fn prev_block_meta<'a>(
-        head_ptr: *mut BlockHead,
-    ) -> (&'a mut BlockHead, &'a mut BlockTail) {
-        let mut prev_tail_ptr: *mut BlockTail =
+    pub(crate) fn prev_block_meta(head_ptr: *mut BlockHead) -> (*mut BlockHead, *mut BlockTail) {
+        let prev_tail_ptr: *mut BlockTail =
             unsafe { byte_sub_into(head_ptr, BLOCK_TAIL_SIZE as _) };
-        let prev_tail = prev_tail_ptr.deref();
-        let mut prev_head_ptr = prev_tail_ptr.head_ptr(prev_tail.size());
-        (prev_head_ptr.deref(), prev_tail)
+        let prev_head_ptr = prev_tail_ptr.head_ptr(prev_tail_ptr.size());
+        (prev_head_ptr, prev_tail_ptr)
+    }
+
+    #[inline(always)]
+    pub(crate) fn mem_ptr(&self) -> *mut u8 {
+        self.storage.mem_ptr()
+    }
+
+    #[inline(always)]
+    pub(crate) fn mem_len(&self) -> Word {
+        self.storage.mem_len()
+    }
+
+    pub(crate) fn mem_bytes(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self.mem_ptr(), self.mem_len() as usize) }
+    }
+
+    pub(crate) fn mem_bytes_mut(&mut self) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(self.mem_ptr(), self.mem_len() as usize) }
     }
 
     fn ptr_eq_mem_start<T>(&self, ptr: *mut T) -> bool {
-        ptr as *const _ == self.mem.as_ptr()
+        ptr as *const _ == self.mem_ptr()
     }
 
     fn ptr_eq_mem_end<T>(&self, ptr: *mut T) -> bool {
-        unsafe { ptr as *const _ == self.mem.as_ptr().add(self.mem.len()) }
+        unsafe { ptr as *const _ == self.mem_ptr().add(self.mem_len() as usize) }
     }
 
     pub(crate) fn is_block_last(&self, head_ptr: *mut BlockHead, block_size: Word) -> bool {
@@ -45,18 +61,18 @@ impl SubAllocator {
     }
 
     pub(crate) fn mem_offset_from_ptr<T>(&self, ptr: *const T) -> Word {
-        (ptr as u64 - self.mem.as_ptr() as u64) as Word
+        (ptr as u64 - self.mem_ptr() as u64) as Word
     }
 
     pub(crate) fn ptr_from_mem_offset<T>(&self, ptr_offset: Word) -> Option<*mut T> {
         match ptr_offset {
             PACKED_NONE_PTR => None,
-            _ => unsafe { Some(self.mem.as_ptr().byte_add(ptr_offset as usize) as _) },
+            _ => unsafe { Some(self.mem_ptr().byte_add(ptr_offset as usize) as _) },
         }
     }
 
     pub(crate) fn ptr_from_mem_offset_unchecked<T>(&self, offset: Word) -> *mut T {
-        unsafe { self.mem.as_ptr().offset(offset as isize) as *mut T }
+        unsafe { self.mem_ptr().offset(offset as isize) as *mut T }
     }
 }
 
@@ -64,7 +80,7 @@ pub(crate) fn left_mask_from(index: Word) -> Word {
     Word::MAX << index
 }
 
-pub(crate) fn align_up(x: Word, align: Word) -> Word {
+pub(crate) const fn align_up(x: Word, align: Word) -> Word {
     (x + align - 1) & !(align - 1)
 }
 
@@ -87,10 +103,6 @@ pub(crate) const fn with_head(size: Word) -> Word {
     size + BLOCK_HEAD_SIZE
 }
 
-pub(crate) const fn with_tail(size: Word) -> Word {
-    size + BLOCK_TAIL_SIZE
-}
-
 pub(crate) const fn size_between_meta_ptrs(
     head_ptr: *const BlockHead,
     tail_ptr: *const BlockTail,
@@ -0,0 +1,57 @@
+use crate::tlsf::Word;
+
+/// Callbacks for allocator events, for profilers, tracing spans or metrics.
+/// Offsets are block offsets as returned by `allocate`, sizes usable bytes.
+/// Every callback defaults to doing nothing, and `()`, the observer of a
+/// plain `SubAllocator`, compiles away entirely.
+///
+/// The heap is a single region fixed at construction, so there are no region
+/// events.
+pub trait AllocObserver {
+    fn on_allocate(&mut self, _offset: Word, _size: Word) {}
+
+    /// called before the block is freed, with its size at that point
+    fn on_deallocate(&mut self, _offset: Word, _size: Word) {}
+
+    /// a free block of `size` bytes at `offset` was split off the block an
+    /// allocation was carved from
+    fn on_split(&mut self, _offset: Word, _size: Word) {}
+
+    /// a block being freed absorbed its free neighbour of `size` bytes at
+    /// `offset`
+    fn on_coalesce(&mut self, _offset: Word, _size: Word) {}
+
+    /// an allocation of `size` bytes found no free block large enough
+    fn on_oom(&mut self, _size: Word) {}
+}
+
+impl AllocObserver for () {}
+
+/// `tracing` feature: emits every event as a `tracing` event with target
+/// `suballoc`, at trace level and OOM at warn level
+#[cfg(feature = "tracing")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TracingObserver;
+
+#[cfg(feature = "tracing")]
+impl AllocObserver for TracingObserver {
+    fn on_allocate(&mut self, offset: Word, size: Word) {
+        tracing::trace!(target: "suballoc", offset, size, "allocate");
+    }
+
+    fn on_deallocate(&mut self, offset: Word, size: Word) {
+        tracing::trace!(target: "suballoc", offset, size, "deallocate");
+    }
+
+    fn on_split(&mut self, offset: Word, size: Word) {
+        tracing::trace!(target: "suballoc", offset, size, "split");
+    }
+
+    fn on_coalesce(&mut self, offset: Word, size: Word) {
+        tracing::trace!(target: "suballoc", offset, size, "coalesce");
+    }
+
+    fn on_oom(&mut self, size: Word) {
+        tracing::warn!(target: "suballoc", size, "out of memory");
+    }
+}
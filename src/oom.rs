@@ -0,0 +1,66 @@
+use crate::observer::AllocObserver;
+use crate::stats::Stats;
+use crate::storage::Storage;
+use crate::tlsf::{SubAllocator, Word};
+use alloc::boxed::Box;
+
+/// how often an allocation is retried after the OOM handler asked for it
+pub const OOM_RETRY_LIMIT: u32 = 8;
+
+/// a failed allocation, as handed to the OOM handler
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OomRequest {
+    /// requested size in bytes
+    pub size: Word,
+    /// retries so far for this request, `0` on the first failure
+    pub attempt: u32,
+    pub stats: Stats,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OomAction {
+    /// search again, the handler freed memory
+    Retry,
+    /// fail the allocation with `OutOfMemory`
+    Fail,
+}
+
+pub(crate) type OomHandler<S, O> = Box<dyn FnMut(&mut SubAllocator<S, O>, OomRequest) -> OomAction>;
+
+impl<S: Storage, O: AllocObserver> SubAllocator<S, O> {
+    /// Installs `handler`, called when an allocation finds no free block. It
+    /// gets the allocator itself, so it can free evictable blocks before
+    /// asking for a retry, up to `OOM_RETRY_LIMIT` times per allocation. While
+    /// it runs the handler is uninstalled: allocations it makes fail plainly
+    /// instead of recursing.
+    pub fn on_out_of_memory(
+        &mut self,
+        handler: impl FnMut(&mut Self, OomRequest) -> OomAction + 'static,
+    ) {
+        self.oom_handler = Some(Box::new(handler));
+    }
+
+    pub fn clear_oom_handler(&mut self) {
+        self.oom_handler = None;
+    }
+
+    /// runs the handler for the `attempt`th failure of a `size` byte request,
+    /// returning whether to search again
+    pub(crate) fn retry_after_oom(&mut self, size: Word, attempt: u32) -> bool {
+        if attempt >= OOM_RETRY_LIMIT {
+            return false;
+        }
+        let Some(mut handler) = self.oom_handler.take() else {
+            return false;
+        };
+        let request = OomRequest {
+            size,
+            attempt,
+            stats: self.stats(),
+        };
+        let action = handler(self, request);
+        // keep a handler installed from inside the call
+        self.oom_handler.get_or_insert(handler);
+        action == OomAction::Retry
+    }
+}
@@ -0,0 +1,97 @@
+//! `op-counts` instrumentation: the allocator counts the primitive steps its
+//! O(1) paths take, so tests can assert a fixed bound per call.
+
+use crate::observer::AllocObserver;
+use crate::storage::Storage;
+use crate::tlsf::SubAllocator;
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Op {
+    /// find first set on a first or second level bitmap
+    BitmapScan,
+    /// write of a free list link or bin head
+    LinkUpdate,
+    /// merge with a free neighbour
+    Coalesce,
+}
+
+/// Primitive steps taken by the allocator, summed since the last
+/// `take_op_counts`.
+///
+/// Worst case per call, independent of heap size and state:
+///
+/// | call                 | bitmap scans | link updates | coalesces |
+/// |----------------------|-------------:|-------------:|----------:|
+/// | `allocate`           |            4 |            5 |         0 |
+/// | `allocate_with_hint` |            4 |            5 |         0 |
+/// | `allocate_zeroed`    |            4 |            5 |         0 |
+/// | `deallocate`         |            0 |            7 |         2 |
+/// | `free`               |            0 |            0 |         0 |
+///
+/// Each step is a handful of instructions with no loops, so a call's WCET is
+/// these counts times the cost of a step on the target, plus a constant.
+/// Outside the bound:
+/// - `guards` fills and checks payloads, O(size)
+/// - `set_scrub_on_free` wipes payloads, O(size)
+/// - with `alloc`, tag and checkpoint records live in B-trees, O(log n) in the
+///   live allocations
+/// - `allocate_at`, `allocate_many`, `deallocate_many`, `reset`, `stats` and
+///   the walkers are linear
+/// - a failing allocation with an OOM handler installed runs the handler and a
+///   `stats` walk per attempt, searching again up to `OOM_RETRY_LIMIT` times;
+///   each search stays within `MAX_ALLOCATE`, the handler's own calls count
+///   on top. Without a handler the failure path is within the bound.
+#[cfg(feature = "op-counts")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OpCounts {
+    pub bitmap_scans: u32,
+    pub link_updates: u32,
+    pub coalesces: u32,
+}
+
+#[cfg(feature = "op-counts")]
+impl OpCounts {
+    pub const MAX_ALLOCATE: Self = Self {
+        bitmap_scans: 4,
+        link_updates: 5,
+        coalesces: 0,
+    };
+    pub const MAX_DEALLOCATE: Self = Self {
+        bitmap_scans: 0,
+        link_updates: 7,
+        coalesces: 2,
+    };
+
+    /// whether every count is at most the one in `bound`
+    pub fn within(&self, bound: &Self) -> bool {
+        self.bitmap_scans <= bound.bitmap_scans
+            && self.link_updates <= bound.link_updates
+            && self.coalesces <= bound.coalesces
+    }
+}
+
+#[cfg(feature = "op-counts")]
+impl<S: Storage, O: AllocObserver> SubAllocator<S, O> {
+    /// counts since the last call, resetting them
+    pub fn take_op_counts(&mut self) -> OpCounts {
+        self.op_counts.take()
+    }
+
+    #[inline(always)]
+    pub(crate) fn count_op(&self, op: Op, n: u32) {
+        self.op_counts.update(|mut counts| {
+            match op {
+                Op::BitmapScan => counts.bitmap_scans += n,
+                Op::LinkUpdate => counts.link_updates += n,
+                Op::Coalesce => counts.coalesces += n,
+            }
+            counts
+        });
+    }
+}
+
+#[cfg(not(feature = "op-counts"))]
+impl<S: Storage, O: AllocObserver> SubAllocator<S, O> {
+    #[inline(always)]
+    pub(crate) fn count_op(&self, _op: Op, _n: u32) {}
+}
@@ -0,0 +1,192 @@
+//! Heap kept in a memory mapped file.
+//!
+//! The file starts with a `FileHeader` holding the magic, format version, word
+//! width, SLI config, a dirty flag, the heap length and the whole `Control`
+//! block, padded to `HEAP_FILE_OFFSET`; the heap follows. Bin heads and free
+//! block links are offsets from heap start, so the file can be mapped anywhere.
+//!
+//! Crash consistency: the dirty flag is raised before every metadata update
+//! and cleared once the heap is consistent again. A file opened with the flag
+//! raised is recovered with `SubAllocator::recover`, which rebuilds tails,
+//! neighbour flags and free lists from the chain of block heads. An allocation
+//! interrupted before its block head was written is lost (the block stays
+//! free); an interrupted deallocation may leave the block used. This covers
+//! process crashes, where the kernel still writes back the mapping; surviving
+//! power loss additionally needs `flush` after the updates that must persist.
+//! Tags, quotas and other per-process state are not stored in the file, and a
+//! file must not be opened by more than one allocator at a time.
+
+use crate::block::BLOCK_ALIGNMENT;
+use crate::mapped::{HeaderIdent, check_mem_len, mapped_mem_len};
+use crate::storage::{Control, Storage};
+use crate::tlsf::{SubAllocator, Word};
+use crate::verify::IntegrityError;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::fd::AsRawFd;
+use std::path::Path;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicU32, Ordering, compiler_fence};
+
+pub const HEAP_FILE_MAGIC: [u8; 8] = *b"SUBAHEAP";
+pub const HEAP_FILE_VERSION: u16 = 4;
+pub const HEAP_FILE_OFFSET: usize = (size_of::<FileHeader>() + 63) & !63;
+
+const KIND: &str = "heap file";
+const IDENT: HeaderIdent = HeaderIdent::new(HEAP_FILE_MAGIC, HEAP_FILE_VERSION);
+
+#[repr(C)]
+struct FileHeader {
+    ident: HeaderIdent,
+    dirty: AtomicU32,
+    mem_len: Word,
+    _reserved: Word,
+    control: Control,
+}
+
+pub struct MappedFile {
+    base: NonNull<u8>,
+    map_len: usize,
+    mem_len: Word,
+    recovered: bool,
+    _file: File,
+}
+
+impl MappedFile {
+    fn map(file: File) -> io::Result<Self> {
+        let map_len = file.metadata()?.len();
+        let mem_len = mapped_mem_len(map_len, HEAP_FILE_OFFSET, KIND)?;
+        let map_len = map_len as usize;
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                map_len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {
+            base: NonNull::new(ptr as *mut u8).unwrap(),
+            map_len,
+            mem_len,
+            recovered: false,
+            _file: file,
+        })
+    }
+
+    fn header(&self) -> *mut FileHeader {
+        self.base.as_ptr() as *mut FileHeader
+    }
+
+    fn validate_header(&self) -> io::Result<bool> {
+        let header = unsafe { &*self.header() };
+        header.ident.check(&IDENT, KIND)?;
+        check_mem_len(header.mem_len, self.mem_len, KIND)?;
+        Ok(header.dirty.load(Ordering::Acquire) != 0)
+    }
+
+    /// writes dirty pages back to the file and waits for completion
+    pub fn flush(&self) -> io::Result<()> {
+        let res = unsafe { libc::msync(self.base.as_ptr() as _, self.map_len, libc::MS_SYNC) };
+        match res {
+            0 => Ok(()),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+
+    /// whether opening the file had to recover an interrupted update
+    pub fn recovered(&self) -> bool {
+        self.recovered
+    }
+}
+
+unsafe impl Storage for MappedFile {
+    fn mem_ptr(&self) -> *mut u8 {
+        unsafe { self.base.as_ptr().add(HEAP_FILE_OFFSET) }
+    }
+
+    fn mem_len(&self) -> Word {
+        self.mem_len
+    }
+
+    fn control_ptr(&self) -> *mut Control {
+        unsafe { &raw mut (*self.header()).control }
+    }
+
+    fn zeroed(&self) -> bool {
+        true
+    }
+
+    fn begin_update(&self) {
+        unsafe { (*self.header()).dirty.store(1, Ordering::SeqCst) };
+        compiler_fence(Ordering::SeqCst);
+    }
+
+    fn end_update(&self) {
+        compiler_fence(Ordering::SeqCst);
+        unsafe { (*self.header()).dirty.store(0, Ordering::SeqCst) };
+    }
+}
+
+impl Drop for MappedFile {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.base.as_ptr() as _, self.map_len) };
+    }
+}
+
+impl SubAllocator<MappedFile> {
+    /// creates a new heap file at `path` holding `capacity` bytes of heap
+    pub fn create_file(path: impl AsRef<Path>, capacity: Word) -> io::Result<Self> {
+        assert_ne!(capacity, 0);
+        assert_eq!(capacity % BLOCK_ALIGNMENT, 0);
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(path)?;
+        file.set_len((HEAP_FILE_OFFSET + capacity as usize) as u64)?;
+
+        let storage = MappedFile::map(file)?;
+        unsafe {
+            storage.header().write(FileHeader {
+                ident: IDENT,
+                dirty: AtomicU32::new(1),
+                mem_len: capacity,
+                _reserved: 0,
+                control: Control::EMPTY,
+            })
+        };
+        let tlsf = Self::with_storage(storage);
+        tlsf.storage.flush()?;
+        Ok(tlsf)
+    }
+
+    /// reopens a heap file, recovering it first if an update was interrupted
+    pub fn open_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        let storage = MappedFile::map(file)?;
+        let dirty = storage.validate_header()?;
+
+        let mut tlsf = Self::from_formatted_storage(storage);
+        if dirty {
+            tlsf.recover().map_err(corrupt_heap)?;
+            tlsf.storage.recovered = true;
+        }
+        tlsf.check_integrity().map_err(corrupt_heap)?;
+        tlsf.update(|tlsf| tlsf.poison_free_blocks());
+        Ok(tlsf)
+    }
+
+    pub fn flush(&self) -> io::Result<()> {
+        self.storage.flush()
+    }
+}
+
+fn corrupt_heap(err: IntegrityError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
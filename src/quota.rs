@@ -0,0 +1,116 @@
+use crate::observer::AllocObserver;
+use crate::storage::Storage;
+use crate::tag::AllocTag;
+use crate::tlsf::{AllocError, AllocResult, SubAllocator, Word};
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TagUsage {
+    pub used: u64,
+    pub high_water: u64,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuotaWarning {
+    pub tag: AllocTag,
+    pub soft_limit: u64,
+    pub used: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Budget {
+    hard: Option<u64>,
+    soft: Option<u64>,
+}
+
+type WarnHook = Box<dyn FnMut(QuotaWarning)>;
+
+#[derive(Default)]
+pub(crate) struct Quotas {
+    budgets: BTreeMap<AllocTag, Budget>,
+    usage: BTreeMap<AllocTag, TagUsage>,
+    warn_hook: Option<WarnHook>,
+}
+
+impl Quotas {
+    pub fn check(&self, tag: AllocTag, bytes: Word) -> AllocResult<()> {
+        let Some(limit) = self.budgets.get(&tag).and_then(|b| b.hard) else {
+            return Ok(());
+        };
+        let used = self.usage.get(&tag).map_or(0, |u| u.used);
+        if used + bytes as u64 > limit {
+            return Err(AllocError::QuotaExceeded { tag, limit, used });
+        }
+        Ok(())
+    }
+
+    pub fn charge(&mut self, tag: AllocTag, bytes: Word) {
+        let usage = self.usage.entry(tag).or_default();
+        let before = usage.used;
+        usage.used += bytes as u64;
+        usage.count += 1;
+        usage.high_water = usage.high_water.max(usage.used);
+
+        let soft = self.budgets.get(&tag).and_then(|b| b.soft);
+        if let (Some(soft_limit), Some(hook)) = (soft, self.warn_hook.as_mut())
+            && before <= soft_limit
+            && usage.used > soft_limit
+        {
+            hook(QuotaWarning {
+                tag,
+                soft_limit,
+                used: usage.used,
+            });
+        }
+    }
+
+    pub fn clear_usage(&mut self) {
+        for usage in self.usage.values_mut() {
+            usage.used = 0;
+            usage.count = 0;
+        }
+    }
+
+    pub fn release(&mut self, tag: AllocTag, bytes: Word) {
+        if let Some(usage) = self.usage.get_mut(&tag) {
+            usage.used -= bytes as u64;
+            usage.count -= 1;
+        }
+    }
+}
+
+impl<S: Storage, O: AllocObserver> SubAllocator<S, O> {
+    /// Hard byte budget for `tag`, allocations past it fail with
+    /// `QuotaExceeded`. Tags are charged their blocks' usable size, guard
+    /// bytes and absorbed leftovers included.
+    pub fn set_quota(&mut self, tag: impl Into<AllocTag>, limit: u64) {
+        self.quotas.budgets.entry(tag.into()).or_default().hard = Some(limit);
+    }
+
+    /// soft byte budget for `tag`, crossing it only invokes the warning hook
+    pub fn set_soft_quota(&mut self, tag: impl Into<AllocTag>, limit: u64) {
+        self.quotas.budgets.entry(tag.into()).or_default().soft = Some(limit);
+    }
+
+    pub fn clear_quota(&mut self, tag: impl Into<AllocTag>) {
+        self.quotas.budgets.remove(&tag.into());
+    }
+
+    pub fn on_soft_quota(&mut self, hook: impl FnMut(QuotaWarning) + 'static) {
+        self.quotas.warn_hook = Some(Box::new(hook));
+    }
+
+    pub fn tag_usage(&self, tag: impl Into<AllocTag>) -> TagUsage {
+        self.quotas
+            .usage
+            .get(&tag.into())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn tag_usages(&self) -> impl Iterator<Item = (AllocTag, TagUsage)> + '_ {
+        self.quotas.usage.iter().map(|(tag, usage)| (*tag, *usage))
+    }
+}
@@ -0,0 +1,236 @@
+use crate::meta::with_meta;
+use crate::observer::AllocObserver;
+use crate::storage::Storage;
+use crate::tlsf::{SLI_SIZE, SubAllocator, WORD_BITS, Word};
+use alloc::string::{String, ToString};
+use alloc::{format, vec, vec::Vec};
+use core::fmt::Write;
+
+const USED_FILL: &str = "#d9534f";
+const FREE_FILL: &str = "#5cb85c";
+const HEAT_CELL_W: u32 = 28;
+const HEAT_CELL_H: u32 = 14;
+const HEAT_MARGIN: u32 = 32;
+
+pub struct RenderOptions<'a> {
+    pub width: u32,
+    pub bar_height: u32,
+    /// per-block label keyed by block offset, defaults to the allocation tag
+    pub labels: Option<&'a dyn Fn(Word) -> Option<String>>,
+}
+
+impl Default for RenderOptions<'_> {
+    fn default() -> Self {
+        Self {
+            width: 1024,
+            bar_height: 48,
+            labels: None,
+        }
+    }
+}
+
+impl<S: Storage, O: AllocObserver> SubAllocator<S, O> {
+    fn block_label(&self, options: &RenderOptions, offset: Word) -> Option<String> {
+        match options.labels {
+            Some(labels) => labels(offset),
+            None => self.tag_of(offset).map(|tag| tag.to_string()),
+        }
+    }
+
+    /// `width` cells of usage bar, at least one, then one line per block
+    pub fn render_ascii(&self, width: usize) -> String {
+        self.render_ascii_with(width, &RenderOptions::default())
+    }
+
+    pub fn render_ascii_with(&self, width: usize, options: &RenderOptions) -> String {
+        let width = width.max(1);
+        let mem_len = self.mem_len() as u64;
+        let cell_start = |i: usize| i as u64 * mem_len / width as u64;
+
+        let mut used_per_cell = vec![0u64; width];
+        for block in self.used_blocks() {
+            let start = block.offset as u64;
+            let end = start + with_meta(block.size) as u64;
+            let first_cell = (start * width as u64 / mem_len) as usize;
+            for (i, used) in used_per_cell.iter_mut().enumerate().skip(first_cell) {
+                let (lo, hi) = (cell_start(i), cell_start(i + 1));
+                if lo >= end {
+                    break;
+                }
+                *used += hi.min(end).saturating_sub(lo.max(start));
+            }
+        }
+
+        let mut out = String::with_capacity(width + 64);
+        out.push('[');
+        for (i, used) in used_per_cell.iter().enumerate() {
+            let cell_len = cell_start(i + 1) - cell_start(i);
+            out.push(match *used {
+                0 => '.',
+                u if u >= cell_len => '#',
+                _ => '+',
+            });
+        }
+        out.push_str("]\n");
+
+        let _ = writeln!(out, "{:>10} {:>10}  {:<5} tag", "offset", "size", "state");
+        for block in self.blocks() {
+            let state = if block.used { "used" } else { "free" };
+            let label = self.block_label(options, block.offset).unwrap_or_default();
+            let line = format!(
+                "{:>10} {:>10}  {:<5} {}",
+                block.offset, block.size, state, label
+            );
+            out.push_str(line.trim_end());
+            out.push('\n');
+        }
+        out
+    }
+
+    pub fn render_svg(&self, options: &RenderOptions) -> String {
+        let mem_len = self.mem_len() as f64;
+        let width = options.width as f64;
+        let bar_h = options.bar_height;
+        let height = bar_h + 40;
+
+        let mut out = String::new();
+        let _ = write!(
+            out,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{height}" viewBox="0 0 {w} {height}">"#,
+            w = options.width
+        );
+        out.push_str(r#"<style>text{font:11px monospace}</style>"#);
+
+        for block in self.blocks() {
+            let total = with_meta(block.size) as f64;
+            let x = block.offset as f64 / mem_len * width;
+            let w = (total / mem_len * width).max(0.5);
+            let (fill, state) = match block.used {
+                true => (USED_FILL, "used"),
+                false => (FREE_FILL, "free"),
+            };
+            let label = self.block_label(options, block.offset);
+            let _ = write!(
+                out,
+                r##"<rect x="{x:.2}" y="0" width="{w:.2}" height="{bar_h}" fill="{fill}" stroke="#ffffff" stroke-width="0.5"><title>offset {}, size {}, {state}{}</title></rect>"##,
+                block.offset,
+                block.size,
+                label
+                    .as_deref()
+                    .map(|l| format!(", {}", xml_escape(l)))
+                    .unwrap_or_default(),
+            );
+            if let Some(label) = label
+                && w >= 7.0 * label.len() as f64
+            {
+                let _ = write!(
+                    out,
+                    r##"<text x="{:.2}" y="{}" fill="#ffffff">{}</text>"##,
+                    x + 2.0,
+                    bar_h / 2 + 4,
+                    xml_escape(&label)
+                );
+            }
+        }
+
+        for quarter in 0..=4u32 {
+            let offset = (self.mem_len() as u64 * quarter as u64 / 4) as Word;
+            let x = width * quarter as f64 / 4.0;
+            let anchor = match quarter {
+                0 => "start",
+                4 => "end",
+                _ => "middle",
+            };
+            let _ = write!(
+                out,
+                r##"<line x1="{x:.2}" y1="{bar_h}" x2="{x:.2}" y2="{}" stroke="#333333"/><text x="{x:.2}" y="{}" text-anchor="{anchor}">{offset}</text>"##,
+                bar_h + 4,
+                bar_h + 16,
+            );
+        }
+        let _ = write!(
+            out,
+            r#"<text x="0" y="{}">capacity {}, free {}</text></svg>"#,
+            bar_h + 34,
+            self.capacity(),
+            self.free(),
+        );
+        out
+    }
+
+    pub fn render_bin_heatmap_svg(&self) -> String {
+        let rows = WORD_BITS as usize;
+        let counts: Vec<[usize; SLI_SIZE]> = (0..rows)
+            .map(|fli| core::array::from_fn(|sli| self.free_list(fli as Word, sli as Word).count()))
+            .collect();
+        let max = counts.iter().flatten().copied().max().unwrap_or(0).max(1);
+
+        let width = HEAT_MARGIN + HEAT_CELL_W * SLI_SIZE as u32;
+        let height = HEAT_MARGIN / 2 + HEAT_CELL_H * rows as u32;
+        let mut out = String::new();
+        let _ = write!(
+            out,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#
+        );
+        out.push_str(r#"<style>text{font:10px monospace}</style>"#);
+        for sli in 0..SLI_SIZE as u32 {
+            let _ = write!(
+                out,
+                r#"<text x="{}" y="11" text-anchor="middle">{sli}</text>"#,
+                HEAT_MARGIN + sli * HEAT_CELL_W + HEAT_CELL_W / 2
+            );
+        }
+        for (fli, row) in counts.iter().enumerate() {
+            let y = HEAT_MARGIN / 2 + fli as u32 * HEAT_CELL_H;
+            let _ = write!(
+                out,
+                r#"<text x="{}" y="{}" text-anchor="end">{fli}</text>"#,
+                HEAT_MARGIN - 4,
+                y + HEAT_CELL_H - 3
+            );
+            for (sli, &count) in row.iter().enumerate() {
+                let x = HEAT_MARGIN + sli as u32 * HEAT_CELL_W;
+                let shade = 255 - (count * 200 / max) as u8;
+                let _ = write!(
+                    out,
+                    r##"<rect x="{x}" y="{y}" width="{HEAT_CELL_W}" height="{HEAT_CELL_H}" fill="#{shade:02x}{shade:02x}ff" stroke="#cccccc"><title>fl {fli}, sl {sli}: {count} free</title></rect>"##
+                );
+                if count != 0 {
+                    let _ = write!(
+                        out,
+                        r#"<text x="{}" y="{}" text-anchor="middle">{count}</text>"#,
+                        x + HEAT_CELL_W / 2,
+                        y + HEAT_CELL_H - 3
+                    );
+                }
+            }
+        }
+        out.push_str("</svg>");
+        out
+    }
+
+    pub fn render_html(&self, options: &RenderOptions) -> String {
+        format!(
+            "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>suballoc heap</title></head><body>\n\
+             <h3>heap</h3>\n{}\n<h3>free bins</h3>\n{}\n<pre>{}</pre>\n</body></html>\n",
+            self.render_svg(options),
+            self.render_bin_heatmap_svg(),
+            xml_escape(&self.render_ascii_with(128, options)),
+        )
+    }
+}
+
+fn xml_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '&' => out.push_str("&amp;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
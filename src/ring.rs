@@ -0,0 +1,359 @@
+//! `RingAllocator`: streaming allocation that advances a head offset through
+//! a ring and reclaims from the tail as the oldest blocks are freed. Every
+//! block starts with an 8 byte header, `[total size | flags, requested size]`,
+//! so the ring describes itself like the TLSF heap does.
+
+use crate::block::{BLOCK_ALIGNMENT, BLOCK_META_SIZE};
+use crate::meta::align_up;
+use crate::stats::Stats;
+use crate::storage::{Control, OwnedStorage, Storage};
+use crate::tlsf::{AllocError, AllocResult, SubAllocator, Word};
+use core::ptr::NonNull;
+
+const HEADER_SIZE: Word = 8;
+const USED: Word = 1;
+/// skipped end of the ring after a wrap around
+const GAP: Word = 2;
+const FLAGS: Word = USED | GAP;
+
+/// what `RingAllocator::deallocate` does with a block that is not the oldest
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FreeOrder {
+    /// fail with `OutOfOrderFree`
+    #[default]
+    Strict,
+    /// mark it freed, its space is reclaimed once every older block is freed
+    Deferred,
+}
+
+/// the part of a `RingAllocator`'s storage behind the ring, managed by its
+/// TLSF fallback with the storage's `Control` block
+pub struct RingFallback {
+    mem: NonNull<u8>,
+    len: Word,
+    ctl: NonNull<Control>,
+    zeroed: bool,
+}
+
+unsafe impl Storage for RingFallback {
+    fn mem_ptr(&self) -> *mut u8 {
+        self.mem.as_ptr()
+    }
+
+    fn mem_len(&self) -> Word {
+        self.len
+    }
+
+    fn control_ptr(&self) -> *mut Control {
+        self.ctl.as_ptr()
+    }
+
+    fn zeroed(&self) -> bool {
+        self.zeroed
+    }
+}
+
+/// Ring buffer allocator over a `Storage`, for staging data that is freed in
+/// about the order it was allocated. Offsets are block offsets like the ones
+/// of `SubAllocator::allocate`; in fallback mode the TLSF part's offsets
+/// follow the ring's.
+pub struct RingAllocator<S: Storage = OwnedStorage> {
+    // dropped before the storage it points into
+    fallback: Option<SubAllocator<RingFallback>>,
+    storage: S,
+    ring_len: Word,
+    /// offset the next block is written at
+    head: Word,
+    /// offset of the oldest block not reclaimed yet
+    tail: Word,
+    /// bytes between tail and head, headers and gap included
+    used: Word,
+    order: FreeOrder,
+}
+
+impl RingAllocator {
+    pub fn new(capacity: Word) -> Self {
+        Self::with_storage(OwnedStorage::new(capacity))
+    }
+}
+
+impl<S: Storage> RingAllocator<S> {
+    /// the whole of `storage` as one ring, its `Control` block unused
+    pub fn with_storage(storage: S) -> Self {
+        let ring_len = storage.mem_len();
+        assert!(ring_len > HEADER_SIZE, "storage too small for a ring");
+        Self {
+            fallback: None,
+            storage,
+            ring_len,
+            head: 0,
+            tail: 0,
+            used: 0,
+            order: FreeOrder::default(),
+        }
+    }
+
+    /// The first `ring_len` bytes of `storage` as the ring and the rest as a
+    /// TLSF heap, which takes `allocate_unordered` calls and the allocations
+    /// the ring has no room for, e.g. while an out of order block pins its
+    /// tail.
+    pub fn with_fallback(storage: S, ring_len: Word) -> Self {
+        assert!(ring_len > HEADER_SIZE && ring_len.is_multiple_of(BLOCK_ALIGNMENT));
+        let rest = storage.mem_len().saturating_sub(ring_len);
+        assert!(rest > BLOCK_META_SIZE, "no room for the fallback heap");
+        let fallback = RingFallback {
+            mem: NonNull::new(unsafe { storage.mem_ptr().add(ring_len as usize) }).unwrap(),
+            len: rest,
+            ctl: NonNull::new(storage.control_ptr()).unwrap(),
+            zeroed: storage.zeroed(),
+        };
+        let mut ring = Self::with_storage(storage);
+        ring.ring_len = ring_len;
+        ring.fallback = Some(SubAllocator::with_storage(fallback));
+        ring
+    }
+
+    pub fn set_free_order(&mut self, order: FreeOrder) {
+        self.order = order;
+    }
+
+    pub fn free_order(&self) -> FreeOrder {
+        self.order
+    }
+
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+
+    pub fn fallback(&self) -> Option<&SubAllocator<RingFallback>> {
+        self.fallback.as_ref()
+    }
+
+    /// largest allocation an empty ring can hold
+    pub fn capacity(&self) -> Word {
+        self.ring_len - HEADER_SIZE
+    }
+
+    /// allocates at the ring's head, wrapping around to the front when the
+    /// end is too short. With a fallback, a full ring allocates from it.
+    pub fn allocate(&mut self, size: Word) -> AllocResult<Word> {
+        debug_assert!(size > 0);
+        match self.push(size) {
+            Err(AllocError::OutOfMemory) if self.fallback.is_some() => {
+                self.allocate_unordered(size)
+            }
+            res => res,
+        }
+    }
+
+    /// allocates from the fallback, for blocks that outlive later ones
+    pub fn allocate_unordered(&mut self, size: Word) -> AllocResult<Word> {
+        let fallback = self.fallback.as_mut().ok_or(AllocError::OutOfMemory)?;
+        Ok(self.ring_len + fallback.allocate(size)?)
+    }
+
+    /// Frees the block at `addr`. The oldest block's space is reclaimed at
+    /// once, along with that of newer blocks already freed; a newer one is
+    /// handled per `FreeOrder`.
+    pub fn deallocate(&mut self, addr: Word) -> AllocResult<()> {
+        if addr >= self.ring_len {
+            let fallback = self
+                .fallback
+                .as_mut()
+                .ok_or(AllocError::InvalidAllocation)?;
+            return fallback.deallocate(addr - self.ring_len);
+        }
+        if addr != self.tail && self.order == FreeOrder::Strict {
+            self.live_header(addr)?;
+            return Err(AllocError::OutOfOrderFree {
+                offset: addr,
+                oldest: self.tail,
+            });
+        }
+        let (word, _) = self.live_header(addr)?;
+        self.write_header(addr, word & !USED, 0);
+        self.reclaim();
+        Ok(())
+    }
+
+    pub fn payload(&self, addr: Word) -> AllocResult<&[u8]> {
+        if addr >= self.ring_len {
+            let fallback = self
+                .fallback
+                .as_ref()
+                .ok_or(AllocError::InvalidAllocation)?;
+            return fallback.payload(addr - self.ring_len);
+        }
+        let (_, requested) = self.live_header(addr)?;
+        let start = (addr + HEADER_SIZE) as usize;
+        Ok(&self.ring_bytes()[start..start + requested as usize])
+    }
+
+    pub fn payload_mut(&mut self, addr: Word) -> AllocResult<&mut [u8]> {
+        if addr >= self.ring_len {
+            let fallback = self
+                .fallback
+                .as_mut()
+                .ok_or(AllocError::InvalidAllocation)?;
+            return fallback.payload_mut(addr - self.ring_len);
+        }
+        let (_, requested) = self.live_header(addr)?;
+        let start = (addr + HEADER_SIZE) as usize;
+        Ok(&mut self.ring_bytes_mut()[start..start + requested as usize])
+    }
+
+    /// frees every ring block at once, and every fallback block
+    pub fn reset(&mut self) {
+        (self.head, self.tail, self.used) = (0, 0, 0);
+        if let Some(fallback) = self.fallback.as_mut() {
+            fallback.reset();
+        }
+    }
+
+    /// The ring's usage, headers excluded. Free blocks are the runs new
+    /// blocks can go to, at most the end and the front of the ring; freed
+    /// blocks waiting for an older one count as neither used nor free.
+    pub fn stats(&self) -> Stats {
+        let mut stats = Stats {
+            capacity: self.capacity(),
+            ..Stats::default()
+        };
+        for (_, word, _) in self.blocks().filter(|&(_, word, _)| word & USED != 0) {
+            stats.used += (word & !FLAGS) - HEADER_SIZE;
+            stats.used_blocks += 1;
+        }
+        for run in self
+            .free_runs()
+            .into_iter()
+            .filter(|&run| run > HEADER_SIZE)
+        {
+            stats.free += run - HEADER_SIZE;
+            stats.free_blocks += 1;
+            stats.largest_free = stats.largest_free.max(run - HEADER_SIZE);
+        }
+        stats
+    }
+
+    /// sizes of the free runs at the head and, once wrapped, the front
+    fn free_runs(&self) -> [Word; 2] {
+        match self.used {
+            0 => [self.ring_len, 0],
+            _ if self.head > self.tail => [self.ring_len - self.head, self.tail],
+            _ => [self.tail - self.head, 0],
+        }
+    }
+
+    fn push(&mut self, size: Word) -> AllocResult<Word> {
+        let len = align_up(size, BLOCK_ALIGNMENT)
+            .checked_add(HEADER_SIZE)
+            .ok_or(AllocError::OutOfMemory)?;
+        let [at_head, at_front] = self.free_runs();
+        let addr = match () {
+            _ if len <= at_head => self.head,
+            _ if len <= at_front => {
+                let gap = self.ring_len - self.head;
+                if gap > 0 {
+                    self.write_header(self.head, gap | GAP, 0);
+                    self.used += gap;
+                }
+                0
+            }
+            _ => return Err(AllocError::OutOfMemory),
+        };
+        self.write_header(addr, len | USED, size);
+        self.head = addr + len;
+        self.used += len;
+        Ok(addr)
+    }
+
+    /// drops freed blocks and gaps off the tail
+    fn reclaim(&mut self) {
+        while self.used > 0 {
+            if self.tail == self.ring_len {
+                self.tail = 0;
+            }
+            let (word, _) = self.header(self.tail);
+            if word & USED != 0 {
+                return;
+            }
+            let len = word & !FLAGS;
+            self.tail += len;
+            self.used -= len;
+        }
+        (self.head, self.tail) = (0, 0);
+    }
+
+    /// `(offset, header word, requested size)` of the blocks and gaps from
+    /// tail to head
+    fn blocks(&self) -> impl Iterator<Item = (Word, Word, Word)> + '_ {
+        let (mut offset, mut left) = (self.tail, self.used);
+        core::iter::from_fn(move || {
+            if left == 0 {
+                return None;
+            }
+            if offset == self.ring_len {
+                offset = 0;
+            }
+            let (word, requested) = self.header(offset);
+            let block = (offset, word, requested);
+            offset += word & !FLAGS;
+            left -= word & !FLAGS;
+            Some(block)
+        })
+    }
+
+    /// Header of the live block starting at `addr`, checked in place: `addr`
+    /// lies in the used span, and the block it describes is used and ends
+    /// within that span. A payload byte pattern passing as a header is not
+    /// told apart from one.
+    fn live_header(&self, addr: Word) -> AllocResult<(Word, Word)> {
+        // end of the used run `addr` is in: the head, or the ring's end
+        // for the part of a wrapped ring behind the tail
+        let run_end = match () {
+            _ if self.used == 0 || !addr.is_multiple_of(BLOCK_ALIGNMENT) => None,
+            _ if self.head > self.tail => {
+                (self.tail..self.head).contains(&addr).then_some(self.head)
+            }
+            _ if addr >= self.tail && addr < self.ring_len => Some(self.ring_len),
+            _ => (addr < self.head).then_some(self.head),
+        };
+        let run_end = run_end.ok_or(AllocError::InvalidAllocation)?;
+        let (word, requested) = self.header(addr);
+        let len = word & !FLAGS;
+        let live = word & FLAGS == USED
+            && len >= HEADER_SIZE
+            && len.is_multiple_of(BLOCK_ALIGNMENT)
+            && len <= run_end - addr
+            && requested <= len - HEADER_SIZE;
+        match live {
+            true => Ok((word, requested)),
+            false => Err(AllocError::InvalidAllocation),
+        }
+    }
+
+    fn ring_bytes(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self.storage.mem_ptr(), self.ring_len as usize) }
+    }
+
+    fn ring_bytes_mut(&mut self) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(self.storage.mem_ptr(), self.ring_len as usize) }
+    }
+
+    fn header(&self, offset: Word) -> (Word, Word) {
+        let [word, requested] = unsafe {
+            self.storage
+                .mem_ptr()
+                .add(offset as usize)
+                .cast::<[Word; 2]>()
+                .read()
+        };
+        (word, requested)
+    }
+
+    fn write_header(&mut self, offset: Word, word: Word, requested: Word) {
+        unsafe {
+            let ptr = self.storage.mem_ptr().add(offset as usize);
+            ptr.cast::<[Word; 2]>().write([word, requested]);
+        }
+    }
+}
@@ -0,0 +1,224 @@
+//! Heap shared between processes.
+//!
+//! The region starts with a `RegionHeader` holding the magic, format version,
+//! word width, SLI config, a futex lock word, the heap length and the whole
+//! `Control` block, padded to `SHARED_REGION_OFFSET`; the heap follows. All
+//! allocator state inside the region is offset based, so every process may map
+//! it at a different address and the offsets returned by `allocate` are valid
+//! in all of them.
+//!
+//! Every metadata update takes the in-region lock, a process shared futex.
+//! Walkers and `check_integrity` do not take it on their own; run them inside
+//! `SubAllocator::locked` to get a consistent view. A process dying while it
+//! holds the lock leaves the region locked. Tags, quotas and leak tracking are
+//! per process state and only see the allocations made through that process.
+
+use crate::block::BLOCK_ALIGNMENT;
+use crate::mapped::{HeaderIdent, check_mem_len, mapped_mem_len};
+use crate::storage::{Control, Storage};
+use crate::tlsf::{SubAllocator, Word};
+use std::io;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd};
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+pub const SHARED_REGION_MAGIC: [u8; 8] = *b"SUBASHRD";
+pub const SHARED_REGION_VERSION: u16 = 4;
+pub const SHARED_REGION_OFFSET: usize = (size_of::<RegionHeader>() + 63) & !63;
+
+const UNLOCKED: u32 = 0;
+const LOCKED: u32 = 1;
+const CONTENDED: u32 = 2;
+
+const KIND: &str = "shared heap region";
+const IDENT: HeaderIdent = HeaderIdent::new(SHARED_REGION_MAGIC, SHARED_REGION_VERSION);
+
+#[repr(C)]
+struct RegionHeader {
+    ident: HeaderIdent,
+    lock: AtomicU32,
+    mem_len: Word,
+    _reserved: Word,
+    control: Control,
+}
+
+pub struct SharedRegion {
+    base: NonNull<u8>,
+    map_len: usize,
+    mem_len: Word,
+    fd: Option<OwnedFd>,
+}
+
+impl SharedRegion {
+    fn map(fd: Option<OwnedFd>, map_len: u64) -> io::Result<Self> {
+        let mem_len = mapped_mem_len(map_len, SHARED_REGION_OFFSET, KIND)?;
+        let map_len = map_len as usize;
+        let (flags, raw_fd) = match &fd {
+            Some(fd) => (libc::MAP_SHARED, fd.as_raw_fd()),
+            None => (libc::MAP_SHARED | libc::MAP_ANONYMOUS, -1),
+        };
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                map_len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                flags,
+                raw_fd,
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {
+            base: NonNull::new(ptr as *mut u8).unwrap(),
+            map_len,
+            mem_len,
+            fd,
+        })
+    }
+
+    fn create(fd: Option<OwnedFd>, capacity: Word) -> io::Result<Self> {
+        assert_ne!(capacity, 0);
+        assert_eq!(capacity % BLOCK_ALIGNMENT, 0);
+        let map_len = SHARED_REGION_OFFSET + capacity as usize;
+        if let Some(fd) = &fd
+            && unsafe { libc::ftruncate(fd.as_raw_fd(), map_len as libc::off_t) } != 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+        let region = Self::map(fd, map_len as u64)?;
+        unsafe {
+            region.header().write(RegionHeader {
+                ident: IDENT,
+                lock: AtomicU32::new(UNLOCKED),
+                mem_len: capacity,
+                _reserved: 0,
+                control: Control::EMPTY,
+            })
+        };
+        Ok(region)
+    }
+
+    fn header(&self) -> *mut RegionHeader {
+        self.base.as_ptr() as *mut RegionHeader
+    }
+
+    fn lock_word(&self) -> &AtomicU32 {
+        unsafe { &(*self.header()).lock }
+    }
+
+    fn validate_header(&self) -> io::Result<()> {
+        let header = unsafe { &*self.header() };
+        header.ident.check(&IDENT, KIND)?;
+        check_mem_len(header.mem_len, self.mem_len, KIND)
+    }
+
+    /// memfd backing the region, `None` for anonymous regions
+    pub fn fd(&self) -> Option<BorrowedFd<'_>> {
+        self.fd.as_ref().map(|fd| fd.as_fd())
+    }
+
+    fn lock(&self) {
+        let lock = self.lock_word();
+        if lock
+            .compare_exchange(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+        {
+            return;
+        }
+        while lock.swap(CONTENDED, Ordering::Acquire) != UNLOCKED {
+            futex(lock, libc::FUTEX_WAIT, CONTENDED);
+        }
+    }
+
+    fn unlock(&self) {
+        let lock = self.lock_word();
+        if lock.swap(UNLOCKED, Ordering::Release) == CONTENDED {
+            futex(lock, libc::FUTEX_WAKE, 1);
+        }
+    }
+}
+
+// no FUTEX_PRIVATE_FLAG, waiters may live in other processes
+fn futex(word: &AtomicU32, op: libc::c_int, val: u32) {
+    unsafe {
+        libc::syscall(
+            libc::SYS_futex,
+            word.as_ptr(),
+            op,
+            val,
+            std::ptr::null::<libc::timespec>(),
+        )
+    };
+}
+
+unsafe impl Storage for SharedRegion {
+    fn mem_ptr(&self) -> *mut u8 {
+        unsafe { self.base.as_ptr().add(SHARED_REGION_OFFSET) }
+    }
+
+    fn mem_len(&self) -> Word {
+        self.mem_len
+    }
+
+    fn control_ptr(&self) -> *mut Control {
+        unsafe { &raw mut (*self.header()).control }
+    }
+
+    fn zeroed(&self) -> bool {
+        true
+    }
+
+    fn begin_update(&self) {
+        self.lock();
+    }
+
+    fn end_update(&self) {
+        self.unlock();
+    }
+}
+
+impl Drop for SharedRegion {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.base.as_ptr() as _, self.map_len) };
+    }
+}
+
+impl SubAllocator<SharedRegion> {
+    /// heap in an anonymous shared mapping, inherited by children across `fork`
+    pub fn new_shared(capacity: Word) -> io::Result<Self> {
+        Ok(Self::with_storage(SharedRegion::create(None, capacity)?))
+    }
+
+    /// heap in a memfd; other processes attach to it through `attach_shared`
+    /// after receiving the descriptor from `storage().fd()`
+    pub fn new_shared_memfd(capacity: Word) -> io::Result<Self> {
+        let fd = unsafe { libc::memfd_create(c"suballoc".as_ptr(), libc::MFD_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        Ok(Self::with_storage(SharedRegion::create(
+            Some(fd),
+            capacity,
+        )?))
+    }
+
+    /// maps a region created by `new_shared_memfd` in this or another process
+    pub fn attach_shared(fd: OwnedFd) -> io::Result<Self> {
+        let map_len = std::fs::File::from(fd.try_clone()?).metadata()?.len();
+        let region = SharedRegion::map(Some(fd), map_len)?;
+        region.validate_header()?;
+        Ok(Self::from_formatted_storage(region))
+    }
+
+    /// runs `f` holding the region lock, so no other process updates the heap
+    /// meanwhile. `f` gets a shared reference and thus cannot allocate.
+    pub fn locked<R>(&self, f: impl FnOnce(&Self) -> R) -> R {
+        self.storage.lock();
+        let result = f(self);
+        self.storage.unlock();
+        result
+    }
+}
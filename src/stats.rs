@@ -0,0 +1,38 @@
+use crate::observer::AllocObserver;
+use crate::storage::Storage;
+use crate::tlsf::{SubAllocator, Word};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub capacity: Word,
+    /// usable bytes in used blocks, block metadata excluded
+    pub used: Word,
+    /// usable bytes in free blocks, block metadata excluded
+    pub free: Word,
+    pub used_blocks: usize,
+    pub free_blocks: usize,
+    pub largest_free: Word,
+}
+
+impl<S: Storage, O: AllocObserver> SubAllocator<S, O> {
+    pub fn stats(&self) -> Stats {
+        let mut stats = Stats {
+            capacity: self.capacity(),
+            ..Stats::default()
+        };
+        for block in self.blocks() {
+            match block.used {
+                true => {
+                    stats.used += block.size;
+                    stats.used_blocks += 1;
+                }
+                false => {
+                    stats.free += block.size;
+                    stats.free_blocks += 1;
+                    stats.largest_free = stats.largest_free.max(block.size);
+                }
+            }
+        }
+        stats
+    }
+}
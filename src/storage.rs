@@ -0,0 +1,192 @@
+use crate::block::{BLOCK_ALIGNMENT, BLOCK_META_SIZE, PACKED_NONE_PTR};
+use crate::tlsf::{SLI_SIZE, WORD_BITS, Word};
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, vec};
+use core::marker::PhantomData;
+use core::ops::Range;
+use core::ptr::NonNull;
+
+/// TLSF bookkeeping kept beside the heap. Bin heads are offsets from mem start,
+/// like the free block links, so the whole state is position independent.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Control {
+    pub(crate) fl_bitmap: Word,
+    pub(crate) sl_bitmaps: [Word; WORD_BITS as usize],
+    pub(crate) free_blocks: [[Word; SLI_SIZE]; WORD_BITS as usize],
+    /// usable bytes of the blocks in the free lists, so `free` needs no walk
+    pub(crate) free_bytes: Word,
+    /// range in which no block was ever handed out, so the heap there is zero
+    /// apart from free block metadata
+    pub(crate) pristine_start: Word,
+    pub(crate) pristine_end: Word,
+}
+
+impl Control {
+    pub const EMPTY: Self = Self {
+        fl_bitmap: 0,
+        sl_bitmaps: [0; WORD_BITS as usize],
+        free_blocks: [[PACKED_NONE_PTR; SLI_SIZE]; WORD_BITS as usize],
+        free_bytes: 0,
+        pristine_start: 0,
+        pristine_end: 0,
+    };
+
+    pub(crate) fn set_pristine(&mut self, range: Range<Word>) {
+        self.pristine_start = range.start;
+        self.pristine_end = range.end;
+    }
+
+    /// records that `start..end` was handed out, keeping the larger pristine
+    /// part beside it; returns whether the whole range was pristine
+    pub(crate) fn take_pristine(&mut self, start: Word, end: Word) -> bool {
+        let (lo, hi) = (self.pristine_start, self.pristine_end);
+        if end <= lo || start >= hi {
+            return false;
+        }
+        match start.saturating_sub(lo) >= hi.saturating_sub(end) {
+            true => self.pristine_end = start.max(lo),
+            false => self.pristine_start = end.min(hi),
+        }
+        lo <= start && end <= hi
+    }
+}
+
+/// whether a heap of `mem_len` bytes, as read from an image or a mapped
+/// header, has room for a free block
+#[cfg(feature = "alloc")]
+pub(crate) fn valid_mem_len(mem_len: Word) -> bool {
+    mem_len > BLOCK_META_SIZE + crate::tlsf::LEFTOVER_ABSORB_SIZE
+        && mem_len.is_multiple_of(BLOCK_ALIGNMENT)
+}
+
+/// Backing memory of a `SubAllocator`: the heap bytes and its `Control` block.
+///
+/// # Safety
+/// `mem_ptr` must be valid for reads and writes of `mem_len` bytes, aligned to
+/// 8, and `mem_len` a non zero multiple of 8. `control_ptr` must be valid and
+/// aligned for a `Control` that does not overlap the heap. Both pointers must
+/// stay the same and remain exclusively owned by the storage while it lives.
+pub unsafe trait Storage {
+    fn mem_ptr(&self) -> *mut u8;
+    fn mem_len(&self) -> Word;
+    fn control_ptr(&self) -> *mut Control;
+
+    /// whether mem reads as all zeroes when `with_storage` formats it
+    fn zeroed(&self) -> bool {
+        false
+    }
+
+    /// called before the allocator starts mutating heap metadata
+    fn begin_update(&self) {}
+    /// called once heap metadata is consistent again
+    fn end_update(&self) {}
+}
+
+/// heap and control block carved from a borrowed buffer, for targets without
+/// a global allocator
+pub struct BorrowedStorage<'a> {
+    mem: NonNull<u8>,
+    mem_len: Word,
+    ctl: NonNull<Control>,
+    _buf: PhantomData<&'a mut [u8]>,
+}
+
+impl<'a> BorrowedStorage<'a> {
+    /// puts the `Control` block at the first 8 aligned byte of `buf` and the
+    /// heap right after it, rounded down to a multiple of 8. Panics if no
+    /// room for a block is left.
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        let ctl_at = buf.as_ptr().align_offset(BLOCK_ALIGNMENT as usize);
+        let mem_at = ctl_at + size_of::<Control>().next_multiple_of(BLOCK_ALIGNMENT as usize);
+        let mem_len = buf.len().saturating_sub(mem_at).min(Word::MAX as usize)
+            & !(BLOCK_ALIGNMENT as usize - 1);
+        assert!(
+            mem_len > BLOCK_META_SIZE as usize,
+            "buffer too small for a heap"
+        );
+        let base = NonNull::from(buf).cast::<u8>();
+        unsafe {
+            let ctl = base.add(ctl_at).cast::<Control>();
+            ctl.write(Control::EMPTY);
+            Self {
+                mem: base.add(mem_at),
+                mem_len: mem_len as Word,
+                ctl,
+                _buf: PhantomData,
+            }
+        }
+    }
+}
+
+unsafe impl Storage for BorrowedStorage<'_> {
+    fn mem_ptr(&self) -> *mut u8 {
+        self.mem.as_ptr()
+    }
+
+    fn mem_len(&self) -> Word {
+        self.mem_len
+    }
+
+    fn control_ptr(&self) -> *mut Control {
+        self.ctl.as_ptr()
+    }
+}
+
+/// heap and control block owned on the global heap
+#[cfg(feature = "alloc")]
+pub struct OwnedStorage {
+    mem: NonNull<[u64]>,
+    ctl: NonNull<Control>,
+}
+
+#[cfg(feature = "alloc")]
+impl OwnedStorage {
+    /// zeroed heap of `len` bytes
+    pub fn new(len: Word) -> Self {
+        assert_ne!(len, 0);
+        assert_eq!(len % BLOCK_ALIGNMENT, 0);
+        let mem = vec![0u64; len as usize / size_of::<u64>()].into_boxed_slice();
+        Self {
+            mem: NonNull::from(Box::leak(mem)),
+            ctl: NonNull::from(Box::leak(Box::new(Control::EMPTY))),
+        }
+    }
+
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Self {
+        let storage = Self::new(bytes.len() as Word);
+        unsafe {
+            core::ptr::copy_nonoverlapping(bytes.as_ptr(), storage.mem_ptr(), bytes.len());
+        }
+        storage
+    }
+}
+
+#[cfg(feature = "alloc")]
+unsafe impl Storage for OwnedStorage {
+    fn mem_ptr(&self) -> *mut u8 {
+        self.mem.as_ptr() as *mut u8
+    }
+
+    fn mem_len(&self) -> Word {
+        (self.mem.len() * size_of::<u64>()) as Word
+    }
+
+    fn control_ptr(&self) -> *mut Control {
+        self.ctl.as_ptr()
+    }
+
+    fn zeroed(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Drop for OwnedStorage {
+    fn drop(&mut self) {
+        unsafe {
+            drop(Box::from_raw(self.mem.as_ptr()));
+            drop(Box::from_raw(self.ctl.as_ptr()));
+        }
+    }
+}
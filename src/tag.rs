@@ -0,0 +1,237 @@
+use core::fmt::Display;
+#[cfg(all(feature = "alloc", feature = "alloc-sites"))]
+use core::panic::Location;
+#[cfg(feature = "alloc")]
+use {
+    crate::block::BLOCK_ALIGNMENT,
+    crate::guard::GUARD_SIZE,
+    crate::meta::align_up,
+    crate::observer::AllocObserver,
+    crate::storage::Storage,
+    crate::tlsf::{AllocResult, SubAllocator, Word},
+    alloc::{boxed::Box, collections::BTreeMap, string::ToString, vec::Vec},
+    core::cmp::Reverse,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AllocTag {
+    Id(u32),
+    Name(&'static str),
+}
+
+impl From<u32> for AllocTag {
+    fn from(id: u32) -> Self {
+        AllocTag::Id(id)
+    }
+}
+
+impl From<&'static str> for AllocTag {
+    fn from(name: &'static str) -> Self {
+        AllocTag::Name(name)
+    }
+}
+
+impl Display for AllocTag {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            AllocTag::Id(id) => write!(f, "#{id}"),
+            AllocTag::Name(name) => f.write_str(name),
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct TagRecord {
+    pub tag: Option<AllocTag>,
+    /// bytes charged against the tag's quota
+    pub charged: Word,
+    #[cfg(feature = "alloc-sites")]
+    pub site: &'static Location<'static>,
+}
+
+#[cfg(feature = "alloc")]
+impl TagRecord {
+    #[cfg_attr(feature = "alloc-sites", track_caller)]
+    fn new(tag: Option<AllocTag>, charged: Word) -> Self {
+        Self {
+            tag,
+            charged,
+            #[cfg(feature = "alloc-sites")]
+            site: Location::caller(),
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+type LeakHook = Box<dyn FnOnce(LeakReport)>;
+
+#[cfg(feature = "alloc")]
+#[derive(Default)]
+pub(crate) struct TagTable {
+    track_all: bool,
+    live: BTreeMap<Word, TagRecord>,
+    drop_hook: Option<LeakHook>,
+}
+
+#[cfg(feature = "alloc")]
+impl TagTable {
+    #[cfg_attr(feature = "alloc-sites", track_caller)]
+    pub fn on_allocate(&mut self, addr: Word, tag: Option<AllocTag>, charged: Word) {
+        if tag.is_some() || self.track_all {
+            self.live.insert(addr, TagRecord::new(tag, charged));
+        }
+    }
+
+    pub fn on_deallocate(&mut self, addr: Word) -> Option<TagRecord> {
+        self.live.remove(&addr)
+    }
+
+    pub fn get(&self, addr: Word) -> Option<&TagRecord> {
+        self.live.get(&addr)
+    }
+
+    pub fn clear(&mut self) {
+        self.live.clear();
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeakEntry {
+    /// `None` for allocations made without a tag
+    pub tag: Option<AllocTag>,
+    /// `None` for allocations made while tracking was off
+    #[cfg(feature = "alloc-sites")]
+    pub site: Option<&'static Location<'static>>,
+    pub count: usize,
+    pub bytes: u64,
+}
+
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LeakReport {
+    /// grouped by tag (and call site), largest byte count first
+    pub entries: Vec<LeakEntry>,
+    pub count: usize,
+    pub bytes: u64,
+}
+
+#[cfg(feature = "alloc")]
+impl LeakReport {
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Display for LeakReport {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        writeln!(
+            f,
+            "{} live allocation(s), {} byte(s)",
+            self.count, self.bytes
+        )?;
+        for entry in &self.entries {
+            let tag = entry
+                .tag
+                .map_or_else(|| "<untagged>".to_string(), |t| t.to_string());
+            write!(f, "{:>8} {:>12}  {}", entry.count, entry.bytes, tag)?;
+            #[cfg(feature = "alloc-sites")]
+            if let Some(site) = entry.site {
+                write!(f, "  at {site}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<S: Storage, O: AllocObserver> SubAllocator<S, O> {
+    #[cfg_attr(feature = "alloc-sites", track_caller)]
+    pub fn allocate_tagged(&mut self, size: Word, tag: impl Into<AllocTag>) -> AllocResult<Word> {
+        let tag = tag.into();
+        // the smallest block `size` can get, failing before the heap is touched
+        self.quotas
+            .check(tag, align_up(size, BLOCK_ALIGNMENT) + GUARD_SIZE)?;
+        let addr = self.allocate_block(size)?;
+        // the block taken, which may have absorbed a small leftover
+        let charged = self.block_size(addr);
+        if let Err(err) = self.quotas.check(tag, charged) {
+            self.undo_allocation(addr, addr);
+            return Err(err);
+        }
+        self.quotas.charge(tag, charged);
+        self.record_allocation(addr, Some(tag), charged);
+        Ok(addr)
+    }
+
+    /// record every allocation, tagged or not, so `leak_report` can attribute it
+    pub fn track_allocations(&mut self, enabled: bool) {
+        self.tags.track_all = enabled;
+    }
+
+    pub fn tag_of(&self, addr: Word) -> Option<AllocTag> {
+        self.tags.get(addr).and_then(|r| r.tag)
+    }
+
+    pub fn leak_report(&self) -> LeakReport {
+        type Key = (Option<AllocTag>, Option<&'static str>, Option<(u32, u32)>);
+        let mut groups: BTreeMap<Key, LeakEntry> = BTreeMap::new();
+        let mut report = LeakReport::default();
+
+        for block in self.used_blocks() {
+            let record = self.tags.get(block.offset);
+            let tag = record.and_then(|r| r.tag);
+            #[cfg(feature = "alloc-sites")]
+            let site = record.map(|r| r.site);
+            #[cfg(feature = "alloc-sites")]
+            let key = (
+                tag,
+                site.map(|s| s.file()),
+                site.map(|s| (s.line(), s.column())),
+            );
+            #[cfg(not(feature = "alloc-sites"))]
+            let key = (tag, None, None);
+
+            let entry = groups.entry(key).or_insert_with(|| LeakEntry {
+                tag,
+                #[cfg(feature = "alloc-sites")]
+                site,
+                count: 0,
+                bytes: 0,
+            });
+            entry.count += 1;
+            entry.bytes += block.size as u64;
+            report.count += 1;
+            report.bytes += block.size as u64;
+        }
+
+        report.entries = groups.into_values().collect();
+        report.entries.sort_by_key(|e| Reverse(e.bytes));
+        report
+    }
+
+    /// hand the leak report to `hook` when the allocator is dropped with live allocations
+    pub fn on_drop_leak_report(&mut self, hook: impl FnOnce(LeakReport) + 'static) {
+        self.tags.drop_hook = Some(Box::new(hook));
+    }
+
+    #[cfg(feature = "std")]
+    pub fn print_leaks_on_drop(&mut self) {
+        self.on_drop_leak_report(|report| std::eprint!("suballoc: leaked {report}"));
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<S: Storage, O: AllocObserver> Drop for SubAllocator<S, O> {
+    fn drop(&mut self) {
+        if let Some(hook) = self.tags.drop_hook.take() {
+            let report = self.leak_report();
+            if !report.is_empty() {
+                hook(report);
+            }
+        }
+    }
+}
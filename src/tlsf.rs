@@ -2,150 +2,354 @@ use crate::block::{
     BLOCK_ALIGNMENT, BLOCK_META_SIZE, BLOCK_TAIL_SIZE, BitFlags, BlockHead, BlockHeadPtrInterface,
     BlockInterface, BlockTail, BlockTailPtrInterface, PACKED_NONE_DOUBLE_PTR, PACKED_NONE_PTR,
 };
+#[cfg(feature = "alloc")]
+use crate::checkpoint::CheckpointLog;
+use crate::guard::GUARD_SIZE;
+use crate::hint::AllocHint;
 use crate::meta::{
     align_up, byte_add_into, byte_sub_into, left_mask_from, size_between_meta_ptrs, strip_meta,
-    with_meta,
+    with_head, with_meta,
 };
-use std::fmt::Debug;
+use crate::observer::AllocObserver;
+#[cfg(feature = "alloc")]
+use crate::oom::OomHandler;
+use crate::ops::Op;
+#[cfg(feature = "op-counts")]
+use crate::ops::OpCounts;
+#[cfg(feature = "alloc")]
+use crate::quota::Quotas;
+use crate::storage::{BorrowedStorage, Control, Storage};
+use crate::tag::AllocTag;
+#[cfg(feature = "alloc")]
+use crate::{storage::OwnedStorage, tag::TagTable};
+#[cfg(feature = "op-counts")]
+use core::cell::Cell;
+use core::fmt::{self, Debug, Display};
+use core::ops::Range;
 
 pub type AllocResult<T> = Result<T, AllocError>;
 pub type Word = u32; // 64bit would require adjusting links to be 64bit
 pub(crate) const WORD_BITS: Word = Word::BITS as Word;
 pub(crate) const SLI_SIZE: usize = 8;
 pub(crate) const SLI_BITS: Word = SLI_SIZE.trailing_zeros() as Word;
+/// leftovers up to this size stay with the used block, free blocks are larger
+pub(crate) const LEFTOVER_ABSORB_SIZE: Word = align_up(BLOCK_META_SIZE + 1, BLOCK_ALIGNMENT);
 
 #[derive(Debug, Clone, Copy)]
 pub enum AllocError {
     OutOfMemory,
     InvalidAllocation,
+    QuotaExceeded {
+        tag: AllocTag,
+        limit: u64,
+        used: u64,
+    },
+    /// `allocate_at`: the requested range overlaps the used block at `offset`
+    RangeInUse {
+        offset: Word,
+        size: Word,
+    },
+    /// `guards` only: the freed block at `offset` was written at `at`
+    UseAfterFree {
+        offset: Word,
+        size: Word,
+        at: Word,
+    },
+    /// `guards` only: the allocation at `offset` of `size` bytes was written
+    /// past its end, first at `at`
+    Overrun {
+        offset: Word,
+        size: Word,
+        at: Word,
+    },
+    /// `RingAllocator` with `FreeOrder::Strict`: the block at `offset` was
+    /// freed while the older one at `oldest` is live
+    OutOfOrderFree {
+        offset: Word,
+        oldest: Word,
+    },
 }
 
-pub struct SubAllocator {
+impl Display for AllocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            AllocError::OutOfMemory => f.write_str("out of memory"),
+            AllocError::InvalidAllocation => f.write_str("invalid allocation"),
+            AllocError::QuotaExceeded { tag, limit, used } => {
+                write!(f, "quota of {tag} exceeded: {used} of {limit} bytes used")
+            }
+            AllocError::RangeInUse { offset, size } => {
+                write!(
+                    f,
+                    "range overlaps the used block at {offset} ({size} bytes)"
+                )
+            }
+            AllocError::UseAfterFree { offset, size, at } => {
+                write!(f, "freed block at {offset} ({size} bytes) written at {at}")
+            }
+            AllocError::Overrun { offset, size, at } => {
+                write!(f, "allocation at {offset} ({size} bytes) overrun at {at}")
+            }
+            AllocError::OutOfOrderFree { offset, oldest } => {
+                write!(
+                    f,
+                    "ring block at {offset} freed before the oldest live block at {oldest}"
+                )
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for AllocError {}
+
+#[cfg(feature = "alloc")]
+pub struct SubAllocator<S: Storage = OwnedStorage, O: AllocObserver = ()> {
     capacity: Word,
-    pub(crate) mem: Box<[u8]>,
-    fl_bitmap: Word,
-    sl_bitmaps: [Word; WORD_BITS as usize],
-    free_blocks: [[Option<*mut BlockHead>; WORD_BITS as usize]; WORD_BITS as usize],
+    pub(crate) scrub_on_free: bool,
+    pub(crate) storage: S,
+    pub(crate) observer: O,
+    pub(crate) tags: TagTable,
+    pub(crate) quotas: Quotas,
+    pub(crate) checkpoints: CheckpointLog,
+    pub(crate) oom_handler: Option<OomHandler<S, O>>,
+    #[cfg(feature = "op-counts")]
+    pub(crate) op_counts: Cell<OpCounts>,
 }
 
+#[cfg(not(feature = "alloc"))]
+pub struct SubAllocator<S: Storage, O: AllocObserver = ()> {
+    capacity: Word,
+    pub(crate) scrub_on_free: bool,
+    pub(crate) storage: S,
+    pub(crate) observer: O,
+    #[cfg(feature = "op-counts")]
+    pub(crate) op_counts: Cell<OpCounts>,
+}
+
+#[cfg(feature = "alloc")]
 impl SubAllocator {
     pub fn new(capacity: Word) -> Self {
         assert_ne!(capacity, 0);
         assert_eq!(capacity % 8, 0);
-        let mem = Self::init_mem(capacity);
-        let mut instance = Self {
-            capacity: strip_meta(mem.len() as Word),
-            mem,
-            fl_bitmap: 0,
-            sl_bitmaps: [0; WORD_BITS as usize],
-            free_blocks: std::array::from_fn(|_| std::array::from_fn(|_| None)),
-        };
-        instance.pushf_free_link(instance.mem.as_ptr() as _);
+        Self::with_storage(OwnedStorage::new(capacity))
+    }
+}
+
+impl<'a> SubAllocator<BorrowedStorage<'a>> {
+    /// heap over a caller provided buffer, see `BorrowedStorage::new`
+    pub fn from_slice(buf: &'a mut [u8]) -> Self {
+        Self::with_storage(BorrowedStorage::new(buf))
+    }
+}
+
+impl<S: Storage> SubAllocator<S> {
+    /// formats `storage` as a single free block
+    pub fn with_storage(storage: S) -> Self {
+        Self::with_observer(storage, ())
+    }
+
+    #[cfg(feature = "alloc")]
+    pub(crate) fn from_formatted_storage(storage: S) -> Self {
+        Self::from_formatted_parts(storage, ())
+    }
+}
+
+impl<S: Storage, O: AllocObserver> SubAllocator<S, O> {
+    /// like `with_storage`, reporting events to `observer`
+    pub fn with_observer(storage: S, observer: O) -> Self {
+        let mut instance = Self::from_formatted_parts(storage, observer);
+        instance.update(|tlsf| {
+            tlsf.init_mem();
+            if tlsf.storage.zeroed() {
+                let mem_len = tlsf.mem_len();
+                tlsf.ctl_mut().set_pristine(0..mem_len);
+            }
+            tlsf.poison_free_blocks();
+        });
         instance
     }
 
-    fn init_mem(capacity: Word) -> Box<[u8]> {
-        let mem = vec![0u8; capacity as usize].into_boxed_slice();
-        let user_size = strip_meta(capacity);
+    /// frees every allocation at once by reformatting mem as a single free
+    /// block. Tag records, quota usage and checkpoints are dropped, budgets
+    /// and high water marks are kept.
+    pub fn reset(&mut self) {
+        self.update(|tlsf| {
+            tlsf.init_mem();
+            tlsf.poison_free_blocks();
+        });
+        #[cfg(feature = "alloc")]
+        {
+            self.tags.clear();
+            self.quotas.clear_usage();
+            self.checkpoints.clear();
+        }
+    }
+
+    pub(crate) fn from_formatted_parts(storage: S, observer: O) -> Self {
+        Self {
+            capacity: strip_meta(storage.mem_len()),
+            scrub_on_free: false,
+            storage,
+            observer,
+            #[cfg(feature = "alloc")]
+            tags: TagTable::default(),
+            #[cfg(feature = "alloc")]
+            quotas: Quotas::default(),
+            #[cfg(feature = "alloc")]
+            checkpoints: CheckpointLog::default(),
+            #[cfg(feature = "alloc")]
+            oom_handler: None,
+            #[cfg(feature = "op-counts")]
+            op_counts: Cell::default(),
+        }
+    }
+
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+
+    pub fn observer(&self) -> &O {
+        &self.observer
+    }
+
+    pub fn observer_mut(&mut self) -> &mut O {
+        &mut self.observer
+    }
+
+    #[inline(always)]
+    pub(crate) fn ctl(&self) -> &Control {
+        unsafe { &*self.storage.control_ptr() }
+    }
+
+    #[inline(always)]
+    pub(crate) fn ctl_mut(&mut self) -> &mut Control {
+        unsafe { &mut *self.storage.control_ptr() }
+    }
 
-        let mut head_ptr = mem.as_ptr() as *mut BlockHead;
-        let head = head_ptr.deref();
-        let tail = head_ptr.tail_ptr(user_size).deref();
+    /// brackets a metadata mutation with the storage's update hooks
+    #[inline(always)]
+    pub(crate) fn update<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        self.storage.begin_update();
+        let result = f(self);
+        self.storage.end_update();
+        result
+    }
+
+    fn init_mem(&mut self) {
+        *self.ctl_mut() = Control::EMPTY;
+        let user_size = strip_meta(self.mem_len());
+
+        let head_ptr = self.mem_ptr() as *mut BlockHead;
+        let tail = head_ptr.tail_ptr(user_size);
 
         let size_flags = user_size | BitFlags::PREV_USED | BitFlags::NEXT_USED;
-        head.set_size_flags(size_flags);
-        head.as_free().set_links(PACKED_NONE_DOUBLE_PTR);
+        head_ptr.set_size_flags(size_flags);
+        head_ptr.set_links(PACKED_NONE_DOUBLE_PTR);
         tail.set_size_flags(size_flags);
 
-        mem
+        self.pushf_free_link(head_ptr);
     }
 
     fn set_bitmap_index_available(&mut self, fli: Word, sli: Word) {
+        let ctl = self.ctl_mut();
         let fl_mask = 1 << fli;
-        self.fl_bitmap |= fl_mask;
+        ctl.fl_bitmap |= fl_mask;
 
         let sl_idx = sli as usize;
         let sl_mask = 1 << sl_idx;
-        self.sl_bitmaps[fli as usize] |= sl_mask;
+        ctl.sl_bitmaps[fli as usize] |= sl_mask;
     }
 
     fn set_bitmap_index_empty(&mut self, fli: Word, sli: Word) {
+        let ctl = self.ctl_mut();
         let sl_idx = sli as usize;
         let sl_mask = 1 << sl_idx;
-        self.sl_bitmaps[fli as usize] &= !sl_mask;
+        ctl.sl_bitmaps[fli as usize] &= !sl_mask;
 
-        if self.sl_bitmaps[fli as usize] == 0 {
+        if ctl.sl_bitmaps[fli as usize] == 0 {
             let fl_mask = 1 << fli;
-            self.fl_bitmap &= !fl_mask;
+            ctl.fl_bitmap &= !fl_mask;
         }
     }
 
-    fn pushf_free_link(&mut self, mut head_ptr: *mut BlockHead) {
-        let head = head_ptr.deref();
-        let (fli, sli) = self.mapping_insert(head.size());
-        let head_free = head.as_free();
+    pub(crate) fn pushf_free_link(&mut self, head_ptr: *mut BlockHead) {
+        let size = head_ptr.size();
+        let (fli, sli) = self.mapping_insert(size);
+        let packed_block_head_ptr = self.mem_offset_from_ptr(head_ptr);
+        self.ctl_mut().free_bytes += size;
 
-        let slot = &mut self.free_blocks[fli as usize][sli as usize];
-        let last_head_opt = std::mem::replace(slot, Some(head_ptr));
+        let slot = &mut self.ctl_mut().free_blocks[fli as usize][sli as usize];
+        let packed_last_head_ptr = core::mem::replace(slot, packed_block_head_ptr);
 
-        match last_head_opt {
-            Some(mut last_head_ptr) => {
+        match self.ptr_from_mem_offset::<BlockHead>(packed_last_head_ptr) {
+            Some(last_head_ptr) => {
                 // pack links
-                let packed_block_head_ptr = self.mem_offset_from_ptr(head_ptr);
-                let packed_last_head_ptr = self.mem_offset_from_ptr(last_head_ptr) as u64;
-                let last_head_free = last_head_ptr.deref().as_free();
-                last_head_free.set_prev_link(packed_block_head_ptr);
-                head_free.set_links(
-                    ((PACKED_NONE_PTR as u64) << (WORD_BITS as u64)) | packed_last_head_ptr,
+                last_head_ptr.set_prev_link(packed_block_head_ptr);
+                head_ptr.set_links(
+                    ((PACKED_NONE_PTR as u64) << (WORD_BITS as u64)) | packed_last_head_ptr as u64,
                 );
             }
-            None => head_free.set_links(PACKED_NONE_DOUBLE_PTR),
+            None => head_ptr.set_links(PACKED_NONE_DOUBLE_PTR),
         }
+        self.count_op(
+            Op::LinkUpdate,
+            2 + (packed_last_head_ptr != PACKED_NONE_PTR) as u32,
+        );
         self.set_bitmap_index_available(fli, sli);
     }
 
-    fn popf_free_link(&mut self, fli: Word, sli: Word) -> *mut BlockHead {
-        let slot_ptr: *mut Option<*mut BlockHead> =
-            &mut self.free_blocks[fli as usize][sli as usize] as *mut _;
-        let mut block_head_ptr = unsafe { (*slot_ptr).take().unwrap() };
+    pub(crate) fn popf_free_link(&mut self, fli: Word, sli: Word) -> *mut BlockHead {
+        let slot = self.ctl().free_blocks[fli as usize][sli as usize];
+        let block_head_ptr = self.ptr_from_mem_offset_unchecked::<BlockHead>(slot);
+        self.ctl_mut().free_bytes -= block_head_ptr.size();
 
         // unpack and set the next link as head
-        let (_, next_link_offset) = block_head_ptr.deref().as_free().link_offsets();
-        let next_link = self.ptr_from_mem_offset::<BlockHead>(next_link_offset);
-        unsafe { *slot_ptr = next_link };
-
-        if let Some(mut next) = next_link {
-            next.deref().as_free().set_prev_link(PACKED_NONE_PTR);
-        } else {
-            self.set_bitmap_index_empty(fli, sli);
+        let (_, next_link_offset) = block_head_ptr.link_offsets();
+        self.ctl_mut().free_blocks[fli as usize][sli as usize] = next_link_offset;
+        self.count_op(
+            Op::LinkUpdate,
+            1 + (next_link_offset != PACKED_NONE_PTR) as u32,
+        );
+
+        match self.ptr_from_mem_offset::<BlockHead>(next_link_offset) {
+            Some(next) => next.set_prev_link(PACKED_NONE_PTR),
+            None => self.set_bitmap_index_empty(fli, sli),
         }
 
         block_head_ptr
     }
 
-    fn remove_free_link(&mut self, fli: Word, sli: Word, head: &mut BlockHead) {
+    pub(crate) fn remove_free_link(&mut self, fli: Word, sli: Word, head_ptr: *mut BlockHead) {
         // unpack links
-        let (prev_link_offset, next_link_offset) = head.as_free().link_offsets();
+        let (prev_link_offset, next_link_offset) = head_ptr.link_offsets();
         let prev_link_opt = self.ptr_from_mem_offset::<BlockHead>(prev_link_offset);
         let next_link_opt = self.ptr_from_mem_offset::<BlockHead>(next_link_offset);
+        self.ctl_mut().free_bytes -= head_ptr.size();
 
         // remove head from linked list
-        if let Some(mut next) = next_link_opt {
-            next.deref().as_free().set_prev_link(prev_link_offset);
+        if let Some(next) = next_link_opt {
+            next.set_prev_link(prev_link_offset);
         }
-        if let Some(mut prev) = prev_link_opt {
-            prev.deref().as_free().set_next_link(next_link_offset);
+        if let Some(prev) = prev_link_opt {
+            prev.set_next_link(next_link_offset);
         }
 
+        let head_offset = self.mem_offset_from_ptr(head_ptr);
         let slot = unsafe {
-            self.free_blocks
+            self.ctl_mut()
+                .free_blocks
                 .get_unchecked_mut(fli as usize)
                 .get_unchecked_mut(sli as usize)
         };
-        if slot.map_or(false, |x| x == head) {
-            *slot = next_link_opt;
+        let is_bin_head = *slot == head_offset;
+        if is_bin_head {
+            *slot = next_link_offset;
         }
-        if slot.is_none() {
+        let empty = *slot == PACKED_NONE_PTR;
+        let links = next_link_opt.is_some() as u32 + prev_link_opt.is_some() as u32;
+        self.count_op(Op::LinkUpdate, links + is_bin_head as u32);
+        if empty {
             self.set_bitmap_index_empty(fli, sli);
         }
     }
@@ -156,17 +360,24 @@ impl SubAllocator {
         (offset << SLI_BITS) >> fl
     }
 
-    fn mapping_search(&self, size: Word) -> AllocResult<(Word, Word)> {
+    pub(crate) fn mapping_search(&self, size: Word) -> AllocResult<(Word, Word)> {
         let fl_idx = (WORD_BITS - 1) - size.leading_zeros() as Word;
-        let available_fl_mask = self.fl_bitmap & left_mask_from(fl_idx);
+        let available_fl_mask = self.ctl().fl_bitmap & left_mask_from(fl_idx);
+        self.count_op(Op::BitmapScan, 1);
         if available_fl_mask == 0 {
             return Err(AllocError::OutOfMemory);
         }
 
         #[inline(always)]
-        fn find_sl_for_fl(this: &SubAllocator, fl_idx: Word, size: Word) -> Option<Word> {
-            let sl_idx = SubAllocator::calc_sl_index_for_fl(size, fl_idx);
-            let available_sl_mask = this.sl_bitmaps[fl_idx as usize] & left_mask_from(sl_idx + 1);
+        fn find_sl_for_fl<S: Storage, O: AllocObserver>(
+            this: &SubAllocator<S, O>,
+            fl_idx: Word,
+            size: Word,
+        ) -> Option<Word> {
+            let sl_idx = SubAllocator::<S, O>::calc_sl_index_for_fl(size, fl_idx);
+            let available_sl_mask =
+                this.ctl().sl_bitmaps[fl_idx as usize] & left_mask_from(sl_idx + 1);
+            this.count_op(Op::BitmapScan, 1);
             if available_sl_mask != 0 {
                 let first_sl = available_sl_mask.trailing_zeros() as Word;
                 return Some(first_sl);
@@ -175,23 +386,25 @@ impl SubAllocator {
         }
 
         let first_fl = available_fl_mask.trailing_zeros() as Word;
-        if first_fl == fl_idx {
-            if let Some(first_sl) = find_sl_for_fl(self, first_fl, size) {
-                return Ok((first_fl, first_sl));
-            }
+        if first_fl == fl_idx
+            && let Some(first_sl) = find_sl_for_fl(self, first_fl, size)
+        {
+            return Ok((first_fl, first_sl));
         }
 
-        let higher_fl_mask = self.fl_bitmap & left_mask_from(fl_idx + 1);
+        let higher_fl_mask = self.ctl().fl_bitmap & left_mask_from(fl_idx + 1);
+        self.count_op(Op::BitmapScan, 1);
         if higher_fl_mask != 0 {
             let next_fl = higher_fl_mask.trailing_zeros();
-            let first_sl = self.sl_bitmaps[next_fl as usize].trailing_zeros() as Word;
+            let first_sl = self.ctl().sl_bitmaps[next_fl as usize].trailing_zeros() as Word;
+            self.count_op(Op::BitmapScan, 1);
             return Ok((next_fl as Word, first_sl));
         }
 
         Err(AllocError::OutOfMemory)
     }
 
-    fn mapping_insert(&mut self, size: Word) -> (Word, Word) {
+    pub(crate) fn mapping_insert(&self, size: Word) -> (Word, Word) {
         let fl_idx = (WORD_BITS - 1) - size.leading_zeros() as Word;
         let sl_idx = Self::calc_sl_index_for_fl(size, fl_idx);
         (fl_idx, sl_idx)
@@ -199,186 +412,349 @@ impl SubAllocator {
 
     fn push_leftover_block(
         &mut self,
-        mut leftover_tail_ptr: *mut BlockTail,
+        leftover_tail_ptr: *mut BlockTail,
         leftover_total_size: Word,
     ) {
         let leftover_use_size = strip_meta(leftover_total_size);
-        let mut leftover_head_ptr = leftover_tail_ptr.head_ptr(leftover_use_size);
+        let leftover_head_ptr = leftover_tail_ptr.head_ptr(leftover_use_size);
 
         let size_flags = leftover_use_size | BitFlags::PREV_USED | BitFlags::NEXT_USED;
-        leftover_head_ptr.deref().set_size_flags(size_flags);
-        leftover_tail_ptr.deref().set_size_flags(size_flags);
+        leftover_head_ptr.set_size_flags(size_flags);
+        leftover_tail_ptr.set_size_flags(size_flags);
 
         self.pushf_free_link(leftover_head_ptr as _);
+        let offset = self.mem_offset_from_ptr(leftover_head_ptr);
+        self.observer.on_split(offset, leftover_use_size);
     }
 
     fn set_next_prev_used(&mut self, head_ptr: *mut BlockHead, block_size: Word) {
         if self.is_block_last(head_ptr as _, block_size) {
             return;
         }
-        let (next_head, next_tail) = unsafe { Self::next_block_meta(head_ptr, block_size) };
-        next_head.or_flags(BitFlags::PREV_USED);
-        next_tail.or_flags(BitFlags::PREV_USED);
+        let (next_head_ptr, next_tail_ptr) = Self::next_block_meta(head_ptr, block_size);
+        next_head_ptr.or_flags(BitFlags::PREV_USED);
+        next_tail_ptr.or_flags(BitFlags::PREV_USED);
     }
 
-    fn set_prev_next_used(&mut self, head_ptr: *mut BlockHead) {
+    pub(crate) fn set_prev_next_used(&mut self, head_ptr: *mut BlockHead) {
         if self.is_block_first(head_ptr as _) {
             return;
         }
-        let (prev_head, prev_tail) = unsafe { Self::prev_block_meta(head_ptr) };
-        prev_head.or_flags(BitFlags::NEXT_USED);
-        prev_tail.or_flags(BitFlags::NEXT_USED);
+        let (prev_head_ptr, prev_tail_ptr) = Self::prev_block_meta(head_ptr);
+        prev_head_ptr.or_flags(BitFlags::NEXT_USED);
+        prev_tail_ptr.or_flags(BitFlags::NEXT_USED);
     }
 
-    fn set_block_used(&mut self, mut head_ptr: *mut BlockHead, used_size: Word) {
-        let head = head_ptr.deref();
-        let block_size = head.size();
+    pub(crate) fn set_block_used(&mut self, head_ptr: *mut BlockHead, used_size: Word) {
+        let block_size = head_ptr.size();
         let leftover_total_size = block_size - used_size;
-        let mut initial_tail_ptr = head_ptr.tail_ptr(block_size);
-
-        let (head, tail, size_flags) =
-            if leftover_total_size <= align_up(BLOCK_META_SIZE + 1, BLOCK_ALIGNMENT) {
-                self.set_next_prev_used(head_ptr, block_size);
-                (
-                    head,
-                    initial_tail_ptr.deref(),
-                    block_size | BitFlags::USED | BitFlags::PREV_USED | BitFlags::NEXT_USED,
-                )
-            } else {
-                self.push_leftover_block(initial_tail_ptr, leftover_total_size);
-                let mut tail_ptr = head_ptr.tail_ptr(used_size);
-                (
-                    head,
-                    tail_ptr.deref(),
-                    used_size | BitFlags::USED | BitFlags::PREV_USED,
-                )
-            };
-        head.set_size_flags(size_flags);
-        tail.set_size_flags(size_flags);
+        let initial_tail_ptr = head_ptr.tail_ptr(block_size);
+
+        let (tail_ptr, size_flags) = if leftover_total_size <= LEFTOVER_ABSORB_SIZE {
+            self.set_next_prev_used(head_ptr, block_size);
+            (
+                initial_tail_ptr,
+                block_size | BitFlags::USED | BitFlags::PREV_USED | BitFlags::NEXT_USED,
+            )
+        } else {
+            self.push_leftover_block(initial_tail_ptr, leftover_total_size);
+            (
+                head_ptr.tail_ptr(used_size),
+                used_size | BitFlags::USED | BitFlags::PREV_USED,
+            )
+        };
+        head_ptr.set_size_flags(size_flags);
+        tail_ptr.set_size_flags(size_flags);
         self.set_prev_next_used(head_ptr);
     }
 
+    #[cfg_attr(feature = "alloc-sites", track_caller)]
     pub fn allocate(&mut self, size: Word) -> AllocResult<Word> {
+        let addr = self.allocate_block(size)?;
+        self.record_allocation(addr, None, 0);
+        Ok(addr)
+    }
+
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(feature = "alloc-sites", track_caller)]
+    pub(crate) fn record_allocation(&mut self, addr: Word, tag: Option<AllocTag>, charged: Word) {
+        self.observer.on_allocate(addr, self.block_size(addr));
+        self.tags.on_allocate(addr, tag, charged);
+        self.checkpoints.on_allocate(addr);
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    #[inline(always)]
+    pub(crate) fn record_allocation(&mut self, addr: Word, _tag: Option<AllocTag>, _charged: Word) {
+        self.observer.on_allocate(addr, self.block_size(addr));
+    }
+
+    pub(crate) fn allocate_block(&mut self, size: Word) -> AllocResult<Word> {
+        self.allocate_fresh_block(size, AllocHint::Low)
+            .map(|(addr, _)| addr)
+    }
+
+    /// also reports whether the block lies in the pristine part of the heap,
+    /// where only the first payload word (the old free links) can be non zero
+    pub(crate) fn allocate_fresh_block(
+        &mut self,
+        size: Word,
+        hint: AllocHint,
+    ) -> AllocResult<(Word, bool)> {
         debug_assert!(size > 0);
-        let aligned_size = align_up(size, BLOCK_ALIGNMENT);
-        let (fli, sli) = self.mapping_search(aligned_size)?;
-        let block_head_ptr = self.popf_free_link(fli, sli);
-        self.set_block_used(block_head_ptr, aligned_size);
-        Ok(self.mem_offset_from_ptr(block_head_ptr))
+        let aligned_size = align_up(size, BLOCK_ALIGNMENT) + GUARD_SIZE;
+        let mut attempt = 0;
+        let (addr, pristine) = loop {
+            match self.take_fresh_block(aligned_size, hint) {
+                Err(AllocError::OutOfMemory) => {
+                    self.observer.on_oom(size);
+                    if !self.retry_after_oom(size, attempt) {
+                        return Err(AllocError::OutOfMemory);
+                    }
+                    attempt += 1;
+                }
+                result => break result?,
+            }
+        };
+        if let Err(err) = self.arm_guard(addr, size) {
+            self.undo_allocation(addr, addr);
+            return Err(err);
+        }
+        Ok((addr, pristine))
+    }
+
+    fn take_fresh_block(
+        &mut self,
+        aligned_size: Word,
+        hint: AllocHint,
+    ) -> AllocResult<(Word, bool)> {
+        self.update(|tlsf| {
+            let (fli, sli) = tlsf.mapping_search(aligned_size)?;
+            let block_head_ptr = tlsf.popf_free_link(fli, sli);
+            let start = tlsf.mem_offset_from_ptr(block_head_ptr);
+            let end = start + with_meta(block_head_ptr.size());
+
+            let high = end - with_meta(aligned_size);
+            let addr = match hint {
+                AllocHint::High if high - start > LEFTOVER_ABSORB_SIZE => high,
+                _ => start,
+            };
+            let block_end = tlsf.carve_block(start..end, addr, aligned_size);
+            Ok((addr, tlsf.ctl_mut().take_pristine(addr, block_end)))
+        })
+    }
+
+    /// marks the block at `addr` used inside the free block `free`, already
+    /// taken off its bin. A gap in front of `addr` becomes a free block of its
+    /// own, one behind it too unless small enough to be absorbed. Returns the
+    /// used block's end.
+    pub(crate) fn carve_block(&mut self, free: Range<Word>, addr: Word, used_size: Word) -> Word {
+        // the front part goes first, marking the used block needs its tail
+        if addr != free.start {
+            let front_head_ptr: *mut BlockHead = self.ptr_from_mem_offset_unchecked(free.start);
+            let front_size = strip_meta(addr - free.start);
+            let size_flags = front_size | BitFlags::PREV_USED | BitFlags::NEXT_USED;
+            front_head_ptr.set_size_flags(size_flags);
+            front_head_ptr
+                .tail_ptr(front_size)
+                .set_size_flags(size_flags);
+            self.pushf_free_link(front_head_ptr);
+            self.observer.on_split(free.start, front_size);
+        }
+
+        let head_ptr: *mut BlockHead = self.ptr_from_mem_offset_unchecked(addr);
+        let rest = strip_meta(free.end - addr);
+        head_ptr.set_size_flags(rest | BitFlags::PREV_USED | BitFlags::NEXT_USED);
+        self.set_block_used(head_ptr, used_size);
+        let block_size = head_ptr.size();
+        if addr != free.start {
+            head_ptr.clear_or_flags(BitFlags::PREV_USED);
+            head_ptr
+                .tail_ptr(block_size)
+                .clear_or_flags(BitFlags::PREV_USED);
+        }
+        addr + with_meta(block_size)
     }
 
     fn coalesce_next(
         &mut self,
         head_ptr: *mut BlockHead,
         tail_ptr: *mut BlockTail,
-        head: &mut BlockHead,
         head_size: Word,
     ) -> *mut BlockTail {
-        let mut next_head_ptr: *mut BlockHead =
+        let next_head_ptr: *mut BlockHead =
             unsafe { byte_add_into(head_ptr, with_meta(head_size) as _) };
-        let next_head = next_head_ptr.deref();
-        let next_head_size = next_head.size();
-        let mut next_tail_ptr = next_head_ptr.tail_ptr(next_head_size);
+        let next_head_size = next_head_ptr.size();
+        let next_tail_ptr = next_head_ptr.tail_ptr(next_head_size);
 
-        match head.next_used() {
+        match head_ptr.next_used() {
             true => {
-                let next_tail = next_tail_ptr.deref();
-                next_head.clear_or_flags(BitFlags::PREV_USED);
-                next_tail.clear_or_flags(BitFlags::PREV_USED);
+                next_head_ptr.clear_or_flags(BitFlags::PREV_USED);
+                next_tail_ptr.clear_or_flags(BitFlags::PREV_USED);
                 tail_ptr
             }
             false => {
                 let (fli, sli) = self.mapping_insert(next_head_size);
-                self.remove_free_link(fli, sli, next_head);
+                self.remove_free_link(fli, sli, next_head_ptr);
+                self.count_op(Op::Coalesce, 1);
+                let offset = self.mem_offset_from_ptr(next_head_ptr);
+                self.observer.on_coalesce(offset, next_head_size);
                 next_tail_ptr
             }
         }
     }
 
-    fn coalesce_prev(&mut self, head_ptr: *mut BlockHead, head: &mut BlockHead) -> *mut BlockHead {
-        let mut prev_tail_ptr: *mut BlockTail =
+    fn coalesce_prev(&mut self, head_ptr: *mut BlockHead) -> *mut BlockHead {
+        let prev_tail_ptr: *mut BlockTail =
             unsafe { byte_sub_into(head_ptr, BLOCK_TAIL_SIZE as usize) };
-        let prev_tail = prev_tail_ptr.deref();
-        let prev_size = prev_tail.size();
-        let mut prev_head_ptr = prev_tail_ptr.head_ptr(prev_size);
-        let prev_head = prev_head_ptr.deref();
+        let prev_size = prev_tail_ptr.size();
+        let prev_head_ptr = prev_tail_ptr.head_ptr(prev_size);
 
-        match head.prev_used() {
+        match head_ptr.prev_used() {
             true => {
-                prev_tail.clear_or_flags(BitFlags::NEXT_USED);
-                prev_head.clear_or_flags(BitFlags::NEXT_USED);
+                prev_tail_ptr.clear_or_flags(BitFlags::NEXT_USED);
+                prev_head_ptr.clear_or_flags(BitFlags::NEXT_USED);
                 head_ptr
             }
             false => {
                 let (fli, sli) = self.mapping_insert(prev_size);
-                self.remove_free_link(fli, sli, prev_head);
+                self.remove_free_link(fli, sli, prev_head_ptr);
+                self.count_op(Op::Coalesce, 1);
+                let offset = self.mem_offset_from_ptr(prev_head_ptr);
+                self.observer.on_coalesce(offset, prev_size);
                 prev_head_ptr
             }
         }
     }
 
     pub fn deallocate(&mut self, addr: Word) -> AllocResult<()> {
-        let mut head_ptr: *mut BlockHead = self.ptr_from_mem_offset_unchecked(addr);
-        let head = head_ptr.deref();
-        debug_assert!(head.flags() & BitFlags::USED == BitFlags::USED);
-        
-        let head_size = head.size();
-        let tail_ptr = head_ptr.tail_ptr(head_size);
-        let mut coalesced_tail_ptr = match self.is_block_last(head_ptr, head_size) {
+        self.check_guard(addr)?;
+        self.observer.on_deallocate(addr, self.block_size(addr));
+        if self.scrub_on_free {
+            self.scrub_payload(addr);
+        }
+        self.update(|tlsf| tlsf.free_run(addr, addr));
+        self.release_record(addr);
+        Ok(())
+    }
+
+    #[cfg(feature = "alloc")]
+    pub(crate) fn release_record(&mut self, addr: Word) {
+        self.checkpoints.on_deallocate(addr);
+        if let Some(record) = self.tags.on_deallocate(addr)
+            && let Some(tag) = record.tag
+        {
+            self.quotas.release(tag, record.charged);
+        }
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    #[inline(always)]
+    pub(crate) fn release_record(&mut self, _addr: Word) {}
+
+    #[cfg(not(feature = "alloc"))]
+    #[inline(always)]
+    pub(crate) fn retry_after_oom(&mut self, _size: Word, _attempt: u32) -> bool {
+        false
+    }
+
+    /// puts back the contiguous blocks from `first` to `last` taken by an
+    /// allocation that failed before recording them, so unlike `deallocate`
+    /// without an `on_deallocate` event
+    pub(crate) fn undo_allocation(&mut self, first: Word, last: Word) {
+        self.update(|tlsf| tlsf.free_run(first, last));
+    }
+
+    /// frees the physically contiguous used blocks from `first` to `last`
+    /// as a single free block
+    pub(crate) fn free_run(&mut self, first: Word, last: Word) {
+        let head_ptr: *mut BlockHead = self.ptr_from_mem_offset_unchecked(first);
+        debug_assert!(head_ptr.flags() & BitFlags::USED == BitFlags::USED);
+        let last_head_ptr: *mut BlockHead = self.ptr_from_mem_offset_unchecked(last);
+        debug_assert!(last_head_ptr.flags() & BitFlags::USED == BitFlags::USED);
+
+        let last_size = last_head_ptr.size();
+        let tail_ptr = last_head_ptr.tail_ptr(last_size);
+        let coalesced_tail_ptr = match self.is_block_last(last_head_ptr, last_size) {
             true => tail_ptr,
-            false => self.coalesce_next(head_ptr, tail_ptr, head, head_size),
+            false => self.coalesce_next(last_head_ptr, tail_ptr, last_size),
         };
 
-        let mut coalesced_head_ptr = match self.is_block_first(head_ptr) {
+        let coalesced_head_ptr = match self.is_block_first(head_ptr) {
             true => head_ptr,
-            false => self.coalesce_prev(head_ptr, head),
+            false => self.coalesce_prev(head_ptr),
         };
 
         let coalesced_size = size_between_meta_ptrs(coalesced_head_ptr, coalesced_tail_ptr);
         let size_flags = coalesced_size | BitFlags::PREV_USED | BitFlags::NEXT_USED;
-        coalesced_head_ptr.deref().set_size_flags(size_flags);
-        coalesced_tail_ptr.deref().set_size_flags(size_flags);
+        coalesced_head_ptr.set_size_flags(size_flags);
+        coalesced_tail_ptr.set_size_flags(size_flags);
 
         self.pushf_free_link(coalesced_head_ptr as _);
+        self.poison_free_block(coalesced_head_ptr);
+    }
 
-        Ok(())
+    /// payload bytes of the used block at `addr`
+    pub fn payload(&self, addr: Word) -> AllocResult<&[u8]> {
+        let range = self.payload_range(addr)?;
+        Ok(&self.mem_bytes()[range])
+    }
+
+    pub fn payload_mut(&mut self, addr: Word) -> AllocResult<&mut [u8]> {
+        let range = self.payload_range(addr)?;
+        Ok(&mut self.mem_bytes_mut()[range])
+    }
+
+    pub(crate) fn payload_range(&self, addr: Word) -> AllocResult<Range<usize>> {
+        let in_heap = addr
+            .checked_add(BLOCK_META_SIZE)
+            .is_some_and(|end| end <= self.mem_len());
+        if !addr.is_multiple_of(BLOCK_ALIGNMENT) || !in_heap {
+            return Err(AllocError::InvalidAllocation);
+        }
+        let head_ptr: *mut BlockHead = self.ptr_from_mem_offset_unchecked(addr);
+        let start = with_head(addr);
+        let len = self.requested_size(addr, head_ptr.size());
+        // an address inside a payload reads user data as the block size
+        let end = start
+            .checked_add(head_ptr.size())
+            .and_then(|end| end.checked_add(BLOCK_TAIL_SIZE));
+        match head_ptr.used() && end.is_some_and(|end| end <= self.mem_len()) {
+            true => Ok(start as usize..(start + len) as usize),
+            false => Err(AllocError::InvalidAllocation),
+        }
+    }
+
+    pub(crate) fn block_size(&self, addr: Word) -> Word {
+        let head_ptr: *mut BlockHead = self.ptr_from_mem_offset_unchecked(addr);
+        head_ptr.size()
     }
 
     pub fn capacity(&self) -> Word {
         self.capacity
     }
 
+    /// usable bytes in free blocks, kept as a running count
     pub fn free(&self) -> Word {
-        let mut total_free: Word = 0;
-        for bin in self.free_blocks.iter().flatten() {
-            let mut link = *bin;
-            while let Some(mut head_ptr) = link {
-                let head = head_ptr.deref();
-                total_free += head.size();
-                let (_, next_link_offset) = head.as_free().link_offsets();
-                let next_link = self.ptr_from_mem_offset::<BlockHead>(next_link_offset);
-                link = next_link;
-            }
-        }
-        total_free
+        self.ctl().free_bytes
     }
 }
 
-impl Debug for SubAllocator {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let (flr, slr) = bitmap_bin_repr(self);
-        write!(f, "user cap: {}, FL: {}\n SL: {}", self.capacity, flr, slr)
+impl<S: Storage, O: AllocObserver> Debug for SubAllocator<S, O> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const BIN_WIDTH: usize = WORD_BITS as usize;
+        let ctl = self.ctl();
+        write!(
+            f,
+            "user cap: {}, FL: {:0BIN_WIDTH$b}\n SL: ",
+            self.capacity, ctl.fl_bitmap
+        )?;
+        for (i, sl) in ctl.sl_bitmaps.iter().enumerate() {
+            if i > 0 {
+                f.write_str("\n")?;
+            }
+            write!(f, "{sl:0BIN_WIDTH$b}")?;
+        }
+        Ok(())
     }
 }
-
-fn bitmap_bin_repr(tlsf: &SubAllocator) -> (String, String) {
-    const BIN_WIDTH: usize = WORD_BITS as usize;
-    let fl_repr = format!("{:0BIN_WIDTH$b}", tlsf.fl_bitmap);
-    let sl_repr = tlsf
-        .sl_bitmaps
-        .iter()
-        .map(|x| format!("{:0BIN_WIDTH$b}", x))
-        .collect::<Vec<_>>()
-        .join("\n");
-    (fl_repr, sl_repr)
-}
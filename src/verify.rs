@@ -0,0 +1,222 @@
+use crate::block::BlockInterface;
+use crate::block::{
+    BLOCK_ALIGNMENT, BLOCK_HEAD_SIZE, BitFlags, BlockHead, BlockHeadPtrInterface, PACKED_NONE_PTR,
+};
+use crate::meta::{left_mask_from, with_meta};
+use crate::observer::AllocObserver;
+use crate::storage::{Control, Storage};
+use crate::tlsf::{SLI_SIZE, SubAllocator, WORD_BITS, Word};
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::fmt::{self, Display};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IntegrityError {
+    /// offset of the offending block, or of the bin head for bin errors
+    pub offset: Word,
+    pub reason: &'static str,
+}
+
+impl Display for IntegrityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "corrupt heap at offset {}: {}", self.offset, self.reason)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for IntegrityError {}
+
+fn corrupt<T>(offset: Word, reason: &'static str) -> Result<T, IntegrityError> {
+    Err(IntegrityError { offset, reason })
+}
+
+impl<S: Storage, O: AllocObserver> SubAllocator<S, O> {
+    fn read_word_at(&self, offset: Word) -> Option<Word> {
+        let start = offset as usize;
+        let bytes = self.mem_bytes().get(start..start + size_of::<Word>())?;
+        Some(Word::from_ne_bytes(bytes.try_into().unwrap()))
+    }
+
+    /// walks every block and free list, checking the heap is well formed
+    pub fn check_integrity(&self) -> Result<(), IntegrityError> {
+        let free_sizes = self.check_physical_blocks()?;
+        self.check_free_bins(&free_sizes)
+    }
+
+    /// usable bytes in free blocks, from a walk of the block chain
+    pub(crate) fn count_free_bytes(&self) -> Result<Word, IntegrityError> {
+        Ok(self.check_physical_blocks()?.values().sum())
+    }
+
+    fn check_physical_blocks(&self) -> Result<BTreeMap<Word, Word>, IntegrityError> {
+        let mem_len = self.mem_len();
+        let mut free_sizes = BTreeMap::new();
+        let mut prev_used = true;
+        let mut expect_used: Option<bool> = None;
+        let mut offset: Word = 0;
+
+        while offset < mem_len {
+            let Some(word) = self.read_word_at(offset) else {
+                return corrupt(offset, "block head out of bounds");
+            };
+            let size = word & BitFlags::SIZE_MASK;
+            if size == 0 || !size.is_multiple_of(BLOCK_ALIGNMENT) {
+                return corrupt(offset, "invalid block size");
+            }
+            let Some(end) = offset
+                .checked_add(with_meta(size))
+                .filter(|&e| e <= mem_len)
+            else {
+                return corrupt(offset, "block overruns heap");
+            };
+            if self.read_word_at(offset + BLOCK_HEAD_SIZE + size) != Some(word) {
+                return corrupt(offset, "block head and tail disagree");
+            }
+
+            let used = word & BitFlags::USED != 0;
+            if expect_used.is_some_and(|expected| expected != used) {
+                return corrupt(offset, "stale next used flag on previous block");
+            }
+            match used {
+                true if (word & BitFlags::PREV_USED != 0) != prev_used => {
+                    return corrupt(offset, "stale prev used flag");
+                }
+                false if !prev_used => return corrupt(offset, "adjacent free blocks"),
+                false => {
+                    let neighbour_flags = BitFlags::PREV_USED | BitFlags::NEXT_USED;
+                    if word & neighbour_flags != neighbour_flags {
+                        return corrupt(offset, "free block missing neighbour flags");
+                    }
+                    free_sizes.insert(offset, size);
+                }
+                _ => {}
+            }
+
+            prev_used = used;
+            expect_used = used.then_some(word & BitFlags::NEXT_USED != 0);
+            offset = end;
+        }
+        if expect_used == Some(false) {
+            return corrupt(offset, "last block expects a next block");
+        }
+        Ok(free_sizes)
+    }
+
+    fn check_free_bins(&self, free_sizes: &BTreeMap<Word, Word>) -> Result<(), IntegrityError> {
+        let mut listed = 0;
+        for fli in 0..WORD_BITS {
+            let sl_bitmap = self.ctl().sl_bitmaps[fli as usize];
+            let fl_set = self.ctl().fl_bitmap & (1 << fli) != 0;
+            if fl_set != (sl_bitmap != 0) {
+                return corrupt(fli, "first level bitmap out of sync");
+            }
+            if sl_bitmap & left_mask_from(SLI_SIZE as Word) != 0 {
+                return corrupt(fli, "second level bitmap out of range");
+            }
+            for sli in 0..SLI_SIZE as Word {
+                let bin = self.ctl().free_blocks[fli as usize][sli as usize];
+                let sl_set = sl_bitmap & (1 << sli) != 0;
+                if sl_set != (bin != PACKED_NONE_PTR) {
+                    return corrupt(fli, "second level bitmap out of sync");
+                }
+                if sl_set && (bin >= self.mem_len() || !bin.is_multiple_of(BLOCK_ALIGNMENT)) {
+                    return corrupt(bin, "bin head outside heap");
+                }
+
+                let mut prev_offset = PACKED_NONE_PTR;
+                let mut next_offset = bin;
+                while next_offset != PACKED_NONE_PTR {
+                    let offset = next_offset;
+                    let Some(&size) = free_sizes.get(&offset) else {
+                        return corrupt(offset, "bin links a non free block");
+                    };
+                    if self.mapping_insert(size) != (fli, sli) {
+                        return corrupt(offset, "free block in wrong bin");
+                    }
+                    listed += 1;
+                    if listed > free_sizes.len() {
+                        return corrupt(offset, "free list cycle");
+                    }
+                    let head_ptr: *mut BlockHead = self.ptr_from_mem_offset_unchecked(offset);
+                    let (prev_link, next_link) = head_ptr.link_offsets();
+                    if prev_link != prev_offset {
+                        return corrupt(offset, "broken prev link");
+                    }
+                    prev_offset = offset;
+                    next_offset = next_link;
+                }
+            }
+        }
+        if listed != free_sizes.len() {
+            return corrupt(0, "free block missing from bins");
+        }
+        if self.ctl().free_bytes != free_sizes.values().sum::<Word>() {
+            return corrupt(0, "free byte count out of sync");
+        }
+        Ok(())
+    }
+
+    /// rebuilds tails, neighbour flags and every free list from the block heads
+    /// alone, merging free neighbours. Recovers a heap whose last update was
+    /// interrupted, as long as the chain of heads is intact.
+    pub fn recover(&mut self) -> Result<(), IntegrityError> {
+        self.update(|tlsf| {
+            tlsf.rebuild_from_heads()?;
+            tlsf.poison_free_blocks();
+            Ok(())
+        })
+    }
+
+    fn rebuild_from_heads(&mut self) -> Result<(), IntegrityError> {
+        let mem_len = self.mem_len();
+        let mut blocks: Vec<(Word, Word, bool)> = Vec::new();
+        let mut offset: Word = 0;
+        while offset < mem_len {
+            let Some(word) = self.read_word_at(offset) else {
+                return corrupt(offset, "block head out of bounds");
+            };
+            let size = word & BitFlags::SIZE_MASK;
+            if size == 0 || !size.is_multiple_of(BLOCK_ALIGNMENT) {
+                return corrupt(offset, "invalid block size");
+            }
+            let Some(end) = offset
+                .checked_add(with_meta(size))
+                .filter(|&e| e <= mem_len)
+            else {
+                return corrupt(offset, "block overruns heap");
+            };
+            let used = word & BitFlags::USED != 0;
+            match blocks.last_mut() {
+                Some((_, last_size, false)) if !used => *last_size += with_meta(size),
+                _ => blocks.push((offset, size, used)),
+            }
+            offset = end;
+        }
+
+        *self.ctl_mut() = Control::EMPTY;
+        for (i, &(offset, size, used)) in blocks.iter().enumerate() {
+            let prev_used = i == 0 || blocks[i - 1].2;
+            let next_used = blocks.get(i + 1).is_none_or(|b| b.2);
+            let size_flags = match used {
+                true => {
+                    let mut flags = size | BitFlags::USED;
+                    if prev_used {
+                        flags |= BitFlags::PREV_USED;
+                    }
+                    if next_used {
+                        flags |= BitFlags::NEXT_USED;
+                    }
+                    flags
+                }
+                false => size | BitFlags::PREV_USED | BitFlags::NEXT_USED,
+            };
+            let head_ptr: *mut BlockHead = self.ptr_from_mem_offset_unchecked(offset);
+            head_ptr.set_size_flags(size_flags);
+            head_ptr.tail_ptr(size).set_size_flags(size_flags);
+            if !used {
+                self.pushf_free_link(head_ptr);
+            }
+        }
+        Ok(())
+    }
+}
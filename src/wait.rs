@@ -0,0 +1,183 @@
+//! `AsyncAllocator`: allocations that wait for memory instead of failing with
+//! `OutOfMemory`, for producers that should back-pressure on a full heap.
+
+use crate::block::BLOCK_ALIGNMENT;
+use crate::guard::GUARD_SIZE;
+use crate::meta::align_up;
+use crate::observer::AllocObserver;
+use crate::storage::{OwnedStorage, Storage};
+use crate::tlsf::{AllocError, AllocResult, SubAllocator, Word};
+use alloc::collections::VecDeque;
+use core::cell::RefCell;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+
+impl<S: Storage, O: AllocObserver> SubAllocator<S, O> {
+    /// whether `allocate(size)` would find a free block, from the bitmaps alone
+    pub(crate) fn fits(&self, size: Word) -> bool {
+        let aligned_size = align_up(size, BLOCK_ALIGNMENT) + GUARD_SIZE;
+        self.mapping_search(aligned_size).is_ok()
+    }
+
+    /// whether `allocate(size)` would succeed on an empty heap. The search only
+    /// takes bins above the request's own, so the whole heap has to be in one.
+    pub(crate) fn could_ever_fit(&self, size: Word) -> bool {
+        let aligned_size = align_up(size, BLOCK_ALIGNMENT) + GUARD_SIZE;
+        self.mapping_insert(aligned_size) < self.mapping_insert(self.capacity())
+    }
+}
+
+struct Waiter {
+    id: u64,
+    size: Word,
+    waker: Waker,
+}
+
+struct Inner<S: Storage, O: AllocObserver> {
+    sa: SubAllocator<S, O>,
+    /// pending requests, served strictly in arrival order
+    waiters: VecDeque<Waiter>,
+    next_id: u64,
+}
+
+impl<S: Storage, O: AllocObserver> Inner<S, O> {
+    /// the front waiter's waker if its request now fits
+    fn ready_front(&self) -> Option<Waker> {
+        let front = self.waiters.front()?;
+        self.sa.fits(front.size).then(|| front.waker.clone())
+    }
+}
+
+/// Shared allocator whose `allocate_async` waits for a large enough free block.
+/// Waiters are served first come first served: a request that would fit does
+/// not overtake an earlier one that does not. `SubAllocator` is not `Send`,
+/// so this is for local executors; share it by reference or `Rc`.
+pub struct AsyncAllocator<S: Storage = OwnedStorage, O: AllocObserver = ()> {
+    inner: RefCell<Inner<S, O>>,
+}
+
+impl<S: Storage, O: AllocObserver> AsyncAllocator<S, O> {
+    pub fn new(sa: SubAllocator<S, O>) -> Self {
+        Self {
+            inner: RefCell::new(Inner {
+                sa,
+                waiters: VecDeque::new(),
+                next_id: 0,
+            }),
+        }
+    }
+
+    pub fn into_inner(self) -> SubAllocator<S, O> {
+        self.inner.into_inner().sa
+    }
+
+    /// Resolves once `size` bytes could be allocated, failing only with errors
+    /// other than `OutOfMemory`, or if `size` exceeds the capacity. Dropping
+    /// the future gives up its place in the queue without leaking.
+    pub fn allocate_async(&self, size: Word) -> AllocateFuture<'_, S, O> {
+        AllocateFuture {
+            pool: self,
+            size,
+            id: None,
+        }
+    }
+
+    /// allocates without waiting, regardless of queued waiters
+    #[cfg_attr(feature = "alloc-sites", track_caller)]
+    pub fn try_allocate(&self, size: Word) -> AllocResult<Word> {
+        self.inner.borrow_mut().sa.allocate(size)
+    }
+
+    /// frees `addr` and wakes the front waiter if it fits now
+    pub fn deallocate(&self, addr: Word) -> AllocResult<()> {
+        self.with(|sa| sa.deallocate(addr))
+    }
+
+    /// runs `f` on the allocator, then wakes the front waiter if it fits now
+    pub fn with<R>(&self, f: impl FnOnce(&mut SubAllocator<S, O>) -> R) -> R {
+        let result = f(&mut self.inner.borrow_mut().sa);
+        self.wake_front();
+        result
+    }
+
+    /// number of pending `allocate_async` calls
+    pub fn waiters(&self) -> usize {
+        self.inner.borrow().waiters.len()
+    }
+
+    // woken outside the borrow, a waker may poll right away
+    fn wake_front(&self) {
+        let waker = self.inner.borrow().ready_front();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// future of `AsyncAllocator::allocate_async`
+pub struct AllocateFuture<'a, S: Storage = OwnedStorage, O: AllocObserver = ()> {
+    pool: &'a AsyncAllocator<S, O>,
+    size: Word,
+    /// queue id once waiting
+    id: Option<u64>,
+}
+
+impl<S: Storage, O: AllocObserver> Future for AllocateFuture<'_, S, O> {
+    type Output = AllocResult<Word>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let mut inner = this.pool.inner.borrow_mut();
+        // with nobody queued a new request goes first, else only the front tries
+        if inner.waiters.front().map(|w| w.id) == this.id {
+            let result = match inner.sa.allocate(this.size) {
+                Err(AllocError::OutOfMemory) if inner.sa.could_ever_fit(this.size) => None,
+                result => Some(result),
+            };
+            if let Some(result) = result {
+                if this.id.take().is_some() {
+                    inner.waiters.pop_front();
+                }
+                drop(inner);
+                // what is left may serve the next waiter too
+                this.pool.wake_front();
+                return Poll::Ready(result);
+            }
+        }
+
+        match this.id {
+            Some(id) => {
+                let waiter = inner.waiters.iter_mut().find(|w| w.id == id).unwrap();
+                waiter.waker.clone_from(cx.waker());
+            }
+            None => {
+                let id = inner.next_id;
+                inner.next_id += 1;
+                inner.waiters.push_back(Waiter {
+                    id,
+                    size: this.size,
+                    waker: cx.waker().clone(),
+                });
+                this.id = Some(id);
+            }
+        }
+        Poll::Pending
+    }
+}
+
+impl<S: Storage, O: AllocObserver> Drop for AllocateFuture<'_, S, O> {
+    fn drop(&mut self) {
+        let Some(id) = self.id else {
+            return;
+        };
+        let mut inner = self.pool.inner.borrow_mut();
+        let was_front = inner.waiters.front().is_some_and(|w| w.id == id);
+        inner.waiters.retain(|w| w.id != id);
+        drop(inner);
+        // the dropped future may have swallowed the wake meant for the front
+        if was_front {
+            self.pool.wake_front();
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use crate::block::{BlockHead, BlockHeadPtrInterface, BlockInterface, PACKED_NONE_PTR};
+use crate::meta::{byte_add_into, with_meta};
+use crate::observer::AllocObserver;
+use crate::storage::{Control, Storage};
+use crate::tlsf::{SLI_SIZE, SubAllocator, WORD_BITS, Word};
+use core::marker::PhantomData;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockInfo {
+    /// offset of the block head from mem start, as returned by `allocate`
+    pub offset: Word,
+    /// usable size, excluding block head and tail
+    pub size: Word,
+    pub used: bool,
+    pub prev_used: bool,
+    pub next_used: bool,
+}
+
+impl BlockInfo {
+    /// offset one past the block tail, i.e. the next block's offset
+    pub fn end(&self) -> Word {
+        self.offset + with_meta(self.size)
+    }
+}
+
+pub struct Blocks<'a> {
+    mem: *mut u8,
+    mem_len: Word,
+    offset: Word,
+    _mem: PhantomData<&'a [u8]>,
+}
+
+impl Iterator for Blocks<'_> {
+    type Item = BlockInfo;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.offset >= self.mem_len {
+            return None;
+        }
+        let head_ptr: *mut BlockHead = unsafe { byte_add_into(self.mem, self.offset as _) };
+        let block = BlockInfo {
+            offset: self.offset,
+            size: head_ptr.size(),
+            used: head_ptr.used(),
+            prev_used: head_ptr.prev_used(),
+            next_used: head_ptr.next_used(),
+        };
+        self.offset = block.end();
+        Some(block)
+    }
+}
+
+pub struct FreeList<'a> {
+    mem: *mut u8,
+    link: Word,
+    _mem: PhantomData<&'a [u8]>,
+}
+
+impl Iterator for FreeList<'_> {
+    type Item = Word;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let offset = self.link;
+        if offset == PACKED_NONE_PTR {
+            return None;
+        }
+        let head_ptr: *mut BlockHead = unsafe { byte_add_into(self.mem, offset as _) };
+        let (_, next_link_offset) = head_ptr.link_offsets();
+        self.link = next_link_offset;
+        Some(offset)
+    }
+}
+
+pub struct FreeBins<'a> {
+    ctl: &'a Control,
+    mem: *mut u8,
+    index: usize,
+}
+
+impl<'a> Iterator for FreeBins<'a> {
+    type Item = (Word, Word, FreeList<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.index < WORD_BITS as usize * SLI_SIZE {
+            let (fli, sli) = (self.index / SLI_SIZE, self.index % SLI_SIZE);
+            self.index += 1;
+            if self.ctl.sl_bitmaps[fli] & (1 << sli) != 0 {
+                let list = FreeList {
+                    mem: self.mem,
+                    link: self.ctl.free_blocks[fli][sli],
+                    _mem: PhantomData,
+                };
+                return Some((fli as Word, sli as Word, list));
+            }
+        }
+        None
+    }
+}
+
+impl<S: Storage, O: AllocObserver> SubAllocator<S, O> {
+    /// walks every block in mem order, used and free
+    pub fn blocks(&self) -> Blocks<'_> {
+        Blocks {
+            mem: self.mem_ptr(),
+            mem_len: self.mem_len(),
+            offset: 0,
+            _mem: PhantomData,
+        }
+    }
+
+    pub fn used_blocks(&self) -> impl Iterator<Item = BlockInfo> + '_ {
+        self.blocks().filter(|b| b.used)
+    }
+
+    pub fn free_blocks(&self) -> impl Iterator<Item = BlockInfo> + '_ {
+        self.blocks().filter(|b| !b.used)
+    }
+
+    /// non-empty free bins as `(fli, sli, block offsets)`
+    pub fn free_bins(&self) -> FreeBins<'_> {
+        FreeBins {
+            ctl: self.ctl(),
+            mem: self.mem_ptr(),
+            index: 0,
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    pub(crate) fn free_list(&self, fli: Word, sli: Word) -> FreeList<'_> {
+        FreeList {
+            mem: self.mem_ptr(),
+            link: self.ctl().free_blocks[fli as usize][sli as usize],
+            _mem: PhantomData,
+        }
+    }
+}
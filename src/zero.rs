@@ -0,0 +1,42 @@
+use crate::hint::AllocHint;
+use crate::observer::AllocObserver;
+use crate::storage::Storage;
+use crate::tlsf::{AllocResult, SubAllocator, Word};
+use core::sync::atomic::{Ordering, compiler_fence};
+
+impl<S: Storage, O: AllocObserver> SubAllocator<S, O> {
+    /// like `allocate`, with the whole payload zeroed. Blocks carved from the
+    /// never used part of a freshly formatted heap skip most of the work.
+    #[cfg_attr(feature = "alloc-sites", track_caller)]
+    pub fn allocate_zeroed(&mut self, size: Word) -> AllocResult<Word> {
+        let (addr, pristine) = self.allocate_fresh_block(size, AllocHint::Low)?;
+        let payload = self.payload_mut(addr)?;
+        match pristine {
+            true => payload[..size_of::<u64>()].fill(0),
+            false => payload.fill(0),
+        }
+        self.record_allocation(addr, None, 0);
+        Ok(addr)
+    }
+
+    /// wipe payloads in `deallocate`, for memory that held secrets
+    pub fn set_scrub_on_free(&mut self, scrub: bool) {
+        self.scrub_on_free = scrub;
+    }
+
+    pub fn scrub_on_free(&self) -> bool {
+        self.scrub_on_free
+    }
+
+    // volatile so the wipe of memory about to be freed is not elided
+    pub(crate) fn scrub_payload(&mut self, addr: Word) {
+        let Ok(range) = self.payload_range(addr) else {
+            return;
+        };
+        let bytes = unsafe { self.mem_ptr().add(range.start) };
+        for i in 0..range.len() {
+            unsafe { bytes.add(i).write_volatile(0) };
+        }
+        compiler_fence(Ordering::SeqCst);
+    }
+}
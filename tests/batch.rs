@@ -0,0 +1,74 @@
+use suballoc::{AllocError, SubAllocator};
+
+#[test]
+fn run_is_carved_back_to_back() {
+    let mut sa = SubAllocator::new(4096);
+    let mut addrs = [0; 6];
+    sa.allocate_many(40, 6, &mut addrs).unwrap();
+    let blocks = sa.used_blocks().collect::<Vec<_>>();
+    assert_eq!(blocks.iter().map(|b| b.offset).collect::<Vec<_>>(), addrs);
+    for pair in blocks.windows(2) {
+        assert_eq!(pair[0].offset + pair[0].size + 16, pair[1].offset);
+    }
+    for &addr in &addrs {
+        assert!(sa.payload(addr).unwrap().len() >= 40);
+    }
+    sa.check_integrity().unwrap();
+}
+
+#[test]
+fn failed_batch_allocates_nothing() {
+    let mut sa = SubAllocator::new(1024);
+    let mut addrs = [0; 20];
+    // too large for one run, and the blocks one by one run out halfway
+    assert!(matches!(
+        sa.allocate_many(100, 20, &mut addrs),
+        Err(AllocError::OutOfMemory)
+    ));
+    assert_eq!(sa.free(), sa.capacity());
+    assert_eq!(sa.used_blocks().count(), 0);
+    sa.check_integrity().unwrap();
+
+    assert!(matches!(
+        sa.allocate_many(0, 2, &mut addrs),
+        Err(AllocError::InvalidAllocation)
+    ));
+    assert!(matches!(
+        sa.allocate_many(8, 21, &mut addrs),
+        Err(AllocError::InvalidAllocation)
+    ));
+}
+
+#[test]
+fn batch_frees_coalesce() {
+    let mut sa = SubAllocator::new(4096);
+    let mut addrs = [0; 8];
+    sa.allocate_many(24, 8, &mut addrs).unwrap();
+    let keep = sa.allocate(64).unwrap();
+
+    // every other block, then the rest: each pass leaves single free blocks
+    let every_other = addrs.iter().step_by(2).copied().collect::<Vec<_>>();
+    let rest = addrs.iter().skip(1).step_by(2).copied().collect::<Vec<_>>();
+    sa.deallocate_many(&every_other).unwrap();
+    assert_eq!(sa.free_blocks().count(), every_other.len() + 1);
+    sa.deallocate_many(&rest).unwrap();
+    assert_eq!(sa.free_blocks().count(), 2);
+
+    sa.deallocate(keep).unwrap();
+    assert_eq!(sa.free_blocks().count(), 1);
+    assert_eq!(sa.free(), sa.capacity());
+}
+
+#[test]
+fn repeated_addresses_free_nothing() {
+    let mut sa = SubAllocator::new(4096);
+    let mut addrs = [0; 3];
+    sa.allocate_many(32, 3, &mut addrs).unwrap();
+    assert!(matches!(
+        sa.deallocate_many(&[addrs[0], addrs[2], addrs[0]]),
+        Err(AllocError::InvalidAllocation)
+    ));
+    assert_eq!(sa.used_blocks().count(), 3);
+    sa.deallocate_many(&addrs).unwrap();
+    assert_eq!(sa.free(), sa.capacity());
+}
@@ -0,0 +1,30 @@
+use suballoc::{Storage, SubAllocator};
+
+#[test]
+fn heap_lives_inside_the_borrowed_buffer() {
+    let mut buf = [0u8; 2051];
+    let range = buf.as_ptr_range();
+    let mut sa = SubAllocator::from_slice(&mut buf[3..]);
+
+    let mem = sa.storage().mem_ptr() as *const u8;
+    let len = sa.storage().mem_len() as usize;
+    assert_eq!(mem as usize % 8, 0);
+    assert!(range.start <= mem && mem.wrapping_add(len) <= range.end);
+    assert!((sa.storage().control_ptr() as *const u8) < mem);
+
+    let a = sa.allocate(100).unwrap();
+    let b = sa.allocate(200).unwrap();
+    sa.payload_mut(a).unwrap().fill(0xab);
+    sa.deallocate(a).unwrap();
+    assert!(sa.payload(b).is_ok());
+    sa.deallocate(b).unwrap();
+    sa.check_integrity().unwrap();
+    assert_eq!(sa.free(), sa.capacity());
+}
+
+#[test]
+#[should_panic]
+fn rejects_buffer_without_room_for_a_heap() {
+    let mut buf = [0u8; 64];
+    SubAllocator::from_slice(&mut buf);
+}
@@ -0,0 +1,108 @@
+use suballoc::{AllocError, SubAllocator, Word};
+
+fn live(sa: &SubAllocator) -> Vec<Word> {
+    sa.used_blocks().map(|b| b.offset).collect()
+}
+
+#[test]
+fn rollback_restores_the_live_blocks() {
+    let mut sa = SubAllocator::new(4096);
+    let kept = [sa.allocate(40).unwrap(), sa.allocate(100).unwrap()];
+    let before = live(&sa);
+
+    let mark = sa.checkpoint();
+    for size in [16, 200, 64, 8] {
+        sa.allocate(size).unwrap();
+    }
+    sa.rollback(mark).unwrap();
+    assert_eq!(live(&sa), before);
+
+    // the mark stays set
+    sa.allocate(300).unwrap();
+    sa.rollback(mark).unwrap();
+    assert_eq!(live(&sa), before);
+    for addr in kept {
+        sa.deallocate(addr).unwrap();
+    }
+    assert_eq!(sa.free(), sa.capacity());
+}
+
+#[test]
+fn nested_marks_roll_back_their_own_span() {
+    let mut sa = SubAllocator::new(4096);
+    let outer = sa.checkpoint();
+    let a = sa.allocate(32).unwrap();
+    let inner = sa.checkpoint();
+    sa.allocate(32).unwrap();
+    sa.allocate(32).unwrap();
+
+    sa.rollback(inner).unwrap();
+    assert_eq!(live(&sa), [a]);
+    sa.allocate(64).unwrap();
+    sa.rollback(outer).unwrap();
+    assert!(live(&sa).is_empty());
+}
+
+#[test]
+fn frees_between_marks_are_not_freed_again() {
+    let mut sa = SubAllocator::new(4096);
+    let old = sa.allocate(48).unwrap();
+    let mark = sa.checkpoint();
+    let a = sa.allocate(48).unwrap();
+    sa.allocate(48).unwrap();
+    sa.deallocate(a).unwrap();
+    // an older block freed after the mark stays freed
+    sa.deallocate(old).unwrap();
+    sa.allocate(48).unwrap();
+
+    sa.rollback(mark).unwrap();
+    assert!(live(&sa).is_empty());
+    assert_eq!(sa.free(), sa.capacity());
+    sa.check_integrity().unwrap();
+}
+
+#[test]
+fn commit_keeps_allocations_and_ends_logging() {
+    let mut sa = SubAllocator::new(4096);
+    let outer = sa.checkpoint();
+    let a = sa.allocate(32).unwrap();
+    let inner = sa.checkpoint();
+    let b = sa.allocate(32).unwrap();
+
+    sa.commit(inner).unwrap();
+    assert!(matches!(
+        sa.rollback(inner),
+        Err(AllocError::InvalidAllocation)
+    ));
+    assert!(matches!(
+        sa.commit(inner),
+        Err(AllocError::InvalidAllocation)
+    ));
+    // the outer mark still covers both
+    sa.rollback(outer).unwrap();
+    assert!(live(&sa).is_empty());
+
+    let c = sa.allocate(32).unwrap();
+    sa.commit(outer).unwrap();
+    assert_eq!(live(&sa), [c]);
+
+    // nothing logged without a mark: a new one only covers what follows
+    let d = sa.allocate(32).unwrap();
+    let mark = sa.checkpoint();
+    sa.allocate(32).unwrap();
+    sa.rollback(mark).unwrap();
+    assert_eq!(live(&sa), [c, d]);
+    assert!(a == c && b == d);
+}
+
+#[test]
+fn reset_drops_marks() {
+    let mut sa = SubAllocator::new(4096);
+    let mark = sa.checkpoint();
+    sa.allocate(32).unwrap();
+    sa.reset();
+    assert!(matches!(
+        sa.rollback(mark),
+        Err(AllocError::InvalidAllocation)
+    ));
+}
@@ -0,0 +1,84 @@
+use suballoc::{AllocError, LifetimeHeaps};
+
+const FRAME: usize = 0;
+const LEVEL: usize = 1;
+
+#[test]
+fn classes_get_their_own_partition() {
+    let mut heaps = LifetimeHeaps::new(&[1024, 4096]);
+    assert_eq!(heaps.classes(), 2);
+    let frame = (0..4)
+        .map(|_| heaps.allocate(FRAME, 64).unwrap())
+        .collect::<Vec<_>>();
+    let level = (0..4)
+        .map(|_| heaps.allocate(LEVEL, 64).unwrap())
+        .collect::<Vec<_>>();
+    assert!(frame.iter().all(|&addr| addr < 1024));
+    assert!(
+        level
+            .iter()
+            .all(|&addr| (1024..1024 + 4096).contains(&addr))
+    );
+    assert!(
+        frame
+            .iter()
+            .all(|&addr| heaps.class_of(addr) == Some(FRAME))
+    );
+    assert!(
+        level
+            .iter()
+            .all(|&addr| heaps.class_of(addr) == Some(LEVEL))
+    );
+    assert_eq!(heaps.class_of(1024 + 4096), None);
+
+    heaps.payload_mut(frame[0]).unwrap().fill(1);
+    heaps.payload_mut(level[0]).unwrap().fill(2);
+    assert!(heaps.payload(frame[0]).unwrap().iter().all(|&b| b == 1));
+    assert!(heaps.payload(level[0]).unwrap().iter().all(|&b| b == 2));
+
+    // filling one class leaves the other's space alone
+    while heaps.allocate(FRAME, 64).is_ok() {}
+    assert!(heaps.allocate(LEVEL, 64).is_ok());
+}
+
+#[test]
+fn stats_and_reset_are_per_class() {
+    let mut heaps = LifetimeHeaps::new(&[1024, 4096]);
+    let frame = heaps.allocate(FRAME, 100).unwrap();
+    heaps.allocate(LEVEL, 200).unwrap();
+    heaps.allocate(LEVEL, 300).unwrap();
+
+    let stats = heaps.stats(FRAME).unwrap();
+    assert_eq!((stats.capacity, stats.used_blocks), (1024 - 16, 1));
+    let stats = heaps.stats(LEVEL).unwrap();
+    assert_eq!((stats.capacity, stats.used_blocks), (4096 - 16, 2));
+
+    heaps.reset(LEVEL).unwrap();
+    assert_eq!(heaps.stats(LEVEL).unwrap().used_blocks, 0);
+    assert_eq!(heaps.stats(FRAME).unwrap().used_blocks, 1);
+    heaps.deallocate(frame).unwrap();
+    let stats = heaps.stats(FRAME).unwrap();
+    assert_eq!(stats.free, stats.capacity);
+}
+
+#[test]
+fn unknown_classes_are_errors() {
+    let mut heaps = LifetimeHeaps::new(&[1024]);
+    assert!(matches!(
+        heaps.allocate(1, 8),
+        Err(AllocError::InvalidAllocation)
+    ));
+    assert!(heaps.stats(1).is_err());
+    assert!(heaps.reset(1).is_err());
+    assert!(heaps.heap(1).is_none());
+    assert!(matches!(
+        heaps.deallocate(4096),
+        Err(AllocError::InvalidAllocation)
+    ));
+}
+
+#[test]
+#[should_panic]
+fn partitions_too_small_for_a_block_are_rejected() {
+    LifetimeHeaps::new(&[1024, 16]);
+}
@@ -0,0 +1,105 @@
+use suballoc::{AllocError, BlockInfo, SubAllocator};
+
+fn layout(sa: &SubAllocator) -> Vec<(u32, u32, bool)> {
+    sa.blocks()
+        .map(|b: BlockInfo| (b.offset, b.size, b.used))
+        .collect()
+}
+
+/// block size a `size` byte request takes, guard bytes included
+fn block_size(size: u32) -> u32 {
+    let mut sa = SubAllocator::new(1024);
+    sa.allocate(size).unwrap();
+    sa.used_blocks().next().unwrap().size
+}
+
+#[test]
+fn block_is_split_out_of_the_middle_of_a_free_one() {
+    let size = block_size(64);
+    let mut sa = SubAllocator::new(1024);
+    assert_eq!(sa.allocate_at(200, 64).unwrap(), 200);
+    let end = 200 + size + 16;
+    assert_eq!(
+        layout(&sa),
+        [
+            (0, 184, false),
+            (200, size, true),
+            (end, 1024 - end - 16, false)
+        ]
+    );
+    sa.check_integrity().unwrap();
+
+    // at the very front, and up to the very end
+    let mut sa = SubAllocator::new(1024);
+    sa.allocate_at(0, 64).unwrap();
+    let last = 1024 - size - 16;
+    sa.allocate_at(last, 64).unwrap();
+    assert_eq!(
+        layout(&sa),
+        [
+            (0, size, true),
+            (size + 16, last - size - 32, false),
+            (last, size, true)
+        ]
+    );
+    sa.check_integrity().unwrap();
+}
+
+#[test]
+fn overlapping_a_used_block_is_range_in_use() {
+    let size = block_size(64);
+    let mut sa = SubAllocator::new(1024);
+    sa.allocate_at(200, 64).unwrap();
+    for offset in [160, 200, 232] {
+        match sa.allocate_at(offset, 64) {
+            Err(AllocError::RangeInUse { offset, size: used }) => {
+                assert_eq!((offset, used), (200, size))
+            }
+            res => panic!("{res:?}"),
+        }
+    }
+    assert_eq!(sa.used_blocks().count(), 1);
+}
+
+#[test]
+fn front_gap_must_hold_a_free_block() {
+    let mut sa = SubAllocator::new(1024);
+    for offset in [8, 16, 24] {
+        assert!(matches!(
+            sa.allocate_at(offset, 32),
+            Err(AllocError::InvalidAllocation)
+        ));
+    }
+    assert_eq!(sa.allocate_at(32, 32).unwrap(), 32);
+    assert_eq!(layout(&sa)[0], (0, 16, false));
+}
+
+#[test]
+fn invalid_requests_are_rejected() {
+    let mut sa = SubAllocator::new(1024);
+    for (offset, size) in [(64, 0), (68, 8), (1024 - 16, 8), (!7, 8)] {
+        assert!(matches!(
+            sa.allocate_at(offset, size),
+            Err(AllocError::InvalidAllocation)
+        ));
+    }
+    assert_eq!(sa.free(), sa.capacity());
+}
+
+#[test]
+fn reserve_range_owns_the_given_bytes() {
+    let mut sa = SubAllocator::new(1024);
+    let addr = sa.reserve_range(512, 100).unwrap();
+    assert_eq!(addr, 512 - 8);
+    assert!(sa.payload(addr).unwrap().len() >= 100);
+    assert!(matches!(
+        sa.reserve_range(4, 8),
+        Err(AllocError::InvalidAllocation)
+    ));
+    assert!(matches!(
+        sa.reserve_range(560, 8),
+        Err(AllocError::RangeInUse { offset: 504, .. })
+    ));
+    sa.deallocate(addr).unwrap();
+    assert_eq!(sa.free(), sa.capacity());
+}
@@ -0,0 +1,110 @@
+use suballoc::{AllocError, POISON, Storage, SubAllocator};
+
+fn scribble(sa: &SubAllocator, at: u32) {
+    unsafe { *sa.storage().mem_ptr().add(at as usize) ^= 0xff };
+}
+
+fn poisoned(sa: &SubAllocator, at: u32, len: usize) -> bool {
+    let mem = unsafe { std::slice::from_raw_parts(sa.storage().mem_ptr(), 1024) };
+    mem[at as usize..at as usize + len]
+        .iter()
+        .all(|&b| b == POISON)
+}
+
+#[test]
+fn payload_ends_at_requested_size() {
+    let mut sa = SubAllocator::new(4096);
+    let a = sa.allocate(13).unwrap();
+    assert_eq!(sa.payload(a).unwrap().len(), 13);
+    sa.payload_mut(a).unwrap().fill(1);
+    sa.check_guards().unwrap();
+    sa.deallocate(a).unwrap();
+    sa.check_integrity().unwrap();
+}
+
+#[test]
+fn overrun_reports_owner_and_offset() {
+    let mut sa = SubAllocator::new(4096);
+    let a = sa.allocate(20).unwrap();
+    let b = sa.allocate(20).unwrap();
+    let end = a + 8 + 20;
+    scribble(&sa, end + 1);
+
+    let expected = (a, 20, end + 1);
+    match sa.check_guards() {
+        Err(AllocError::Overrun { offset, size, at }) => assert_eq!((offset, size, at), expected),
+        res => panic!("{res:?}"),
+    }
+    match sa.deallocate(a) {
+        Err(AllocError::Overrun { offset, size, at }) => assert_eq!((offset, size, at), expected),
+        res => panic!("{res:?}"),
+    }
+    sa.deallocate(b).unwrap();
+}
+
+#[test]
+fn write_after_free_is_caught_on_reuse() {
+    let mut sa = SubAllocator::new(1024);
+    let _before = sa.allocate(64).unwrap();
+    let victim = sa.allocate(120).unwrap();
+    while sa.allocate(64).is_ok() {}
+    sa.deallocate(victim).unwrap();
+    assert!(sa.payload(victim).is_err());
+    assert!(poisoned(&sa, victim + 16, 112));
+    scribble(&sa, victim + 8 + 40);
+
+    let free = sa.free();
+    match sa.allocate(64) {
+        Err(AllocError::UseAfterFree { offset, at, .. }) => {
+            assert_eq!((offset, at), (victim, victim + 8 + 40))
+        }
+        res => panic!("{res:?}"),
+    }
+    // the dirty block went back to the free list, repainted
+    assert_eq!(sa.free(), free);
+    sa.check_integrity().unwrap();
+    assert!(sa.allocate(64).is_ok());
+}
+
+#[test]
+fn run_with_a_dirty_block_is_undone() {
+    let mut sa = SubAllocator::new(1024);
+    scribble(&sa, 200);
+    let mut addrs = [0; 4];
+    assert!(matches!(
+        sa.allocate_many(64, 4, &mut addrs),
+        Err(AllocError::UseAfterFree { at: 200, .. })
+    ));
+    assert_eq!(sa.free(), sa.capacity());
+    assert_eq!(sa.used_blocks().count(), 0);
+    sa.check_integrity().unwrap();
+}
+
+#[test]
+fn freeing_a_non_block_is_an_error() {
+    let mut sa = SubAllocator::new(1024);
+    let a = sa.allocate(64).unwrap();
+    assert!(matches!(
+        sa.deallocate(a + 8),
+        Err(AllocError::InvalidAllocation)
+    ));
+    sa.deallocate(a).unwrap();
+    assert!(matches!(
+        sa.deallocate(a),
+        Err(AllocError::InvalidAllocation)
+    ));
+    assert_eq!(sa.free(), sa.capacity());
+}
+
+#[test]
+fn dirty_fixed_block_is_put_back() {
+    let mut sa = SubAllocator::new(1024);
+    scribble(&sa, 240);
+    assert!(matches!(
+        sa.allocate_at(200, 64),
+        Err(AllocError::UseAfterFree { offset: 200, .. })
+    ));
+    assert_eq!(sa.free(), sa.capacity());
+    assert_eq!(sa.used_blocks().count(), 0);
+    sa.check_integrity().unwrap();
+}
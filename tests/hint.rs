@@ -0,0 +1,60 @@
+use suballoc::{AllocHint, SubAllocator};
+
+const MEM_LEN: u32 = 4096;
+// capacity 3840, the start of a second level class, so a request a little
+// smaller rounds up to the class the whole heap is in
+const CLASS_MEM_LEN: u32 = 3856;
+
+// usable size of the block serving `size`, guard bytes included
+fn block_size(size: u32) -> u32 {
+    let mut sa = SubAllocator::new(1024);
+    sa.allocate(size).unwrap();
+    sa.used_blocks().next().unwrap().size
+}
+
+#[test]
+fn high_takes_the_top_of_the_heap() {
+    let mut sa = SubAllocator::new(MEM_LEN);
+    let capacity = sa.capacity();
+    let high = sa.allocate_with_hint(100, AllocHint::High).unwrap();
+    assert_eq!(high, capacity - block_size(100));
+    assert_eq!(sa.used_blocks().next().unwrap().end(), MEM_LEN);
+    let free = sa.free_blocks().map(|b| b.offset).collect::<Vec<_>>();
+    assert_eq!(free, [0]);
+
+    let low = sa.allocate_with_hint(100, AllocHint::Low).unwrap();
+    assert_eq!(low, 0);
+    sa.check_integrity().unwrap();
+}
+
+#[test]
+fn high_absorbs_a_small_leftover() {
+    let mut sa = SubAllocator::new(CLASS_MEM_LEN);
+    let capacity = sa.capacity();
+    let guard = block_size(8) - 8;
+    // a 16 byte leftover is too small to be a block of its own
+    let size = capacity - 16 - guard;
+    let addr = sa.allocate_with_hint(size, AllocHint::High).unwrap();
+    assert_eq!(addr, 0);
+    assert_eq!(sa.used_blocks().next().unwrap().size, capacity);
+    assert_eq!(sa.free_blocks().count(), 0);
+}
+
+#[test]
+fn zeroed_allocation_after_high() {
+    let mut sa = SubAllocator::new(CLASS_MEM_LEN);
+    let high = sa.allocate_with_hint(512, AllocHint::High).unwrap();
+    sa.payload_mut(high).unwrap().fill(0xff);
+
+    // the front is still pristine
+    let low = sa.allocate_zeroed(512).unwrap();
+    assert_eq!(low, 0);
+    assert!(sa.payload(low).unwrap().iter().all(|&byte| byte == 0));
+    sa.deallocate(low).unwrap();
+
+    // a block reaching into the dirty top is not taken for a pristine one
+    sa.deallocate(high).unwrap();
+    let size = sa.capacity() - 16 - (block_size(8) - 8);
+    let all = sa.allocate_zeroed(size).unwrap();
+    assert!(sa.payload(all).unwrap().iter().all(|&byte| byte == 0));
+}
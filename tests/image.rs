@@ -0,0 +1,164 @@
+use suballoc::{IMAGE_VERSION, RestoreError, SubAllocator, Word};
+
+const MEM_LEN_AT: usize = 16;
+/// `serialize_metadata`: block count, then the block words
+const BLOCK_COUNT_AT: usize = MEM_LEN_AT + 8 + 32 * 4;
+
+fn heap() -> SubAllocator {
+    let mut sa = SubAllocator::new(1024);
+    let a = sa.allocate(64).unwrap();
+    sa.allocate(128).unwrap();
+    sa.deallocate(a).unwrap();
+    sa
+}
+
+fn put_word(image: &mut [u8], at: usize, word: Word) {
+    image[at..at + 4].copy_from_slice(&word.to_le_bytes());
+}
+
+fn word(image: &[u8], at: usize) -> Word {
+    Word::from_le_bytes(image[at..at + 4].try_into().unwrap())
+}
+
+fn restore_err(image: &[u8]) -> RestoreError {
+    match SubAllocator::restore(image) {
+        Ok(_) => panic!("image was accepted"),
+        Err(err) => err,
+    }
+}
+
+#[test]
+fn round_trips() {
+    let sa = heap();
+    for image in [sa.serialize(), sa.serialize_metadata()] {
+        let restored = SubAllocator::restore(&image).unwrap();
+        assert_eq!(restored.stats(), sa.stats());
+    }
+}
+
+#[test]
+fn rejects_bad_magic() {
+    let mut image = heap().serialize();
+    image[0] ^= 1;
+    assert_eq!(restore_err(&image), RestoreError::BadMagic);
+}
+
+#[test]
+fn rejects_other_versions() {
+    let mut image = heap().serialize();
+    image[8..10].copy_from_slice(&(IMAGE_VERSION + 1).to_le_bytes());
+    assert_eq!(
+        restore_err(&image),
+        RestoreError::UnsupportedVersion(IMAGE_VERSION + 1)
+    );
+}
+
+#[test]
+fn rejects_other_word_widths() {
+    let mut image = heap().serialize();
+    image[10] = 64;
+    assert_eq!(restore_err(&image), RestoreError::WordWidth(64));
+}
+
+#[test]
+fn rejects_other_sli_configs() {
+    let mut image = heap().serialize();
+    image[11] = 16;
+    assert_eq!(restore_err(&image), RestoreError::SliConfig(16));
+}
+
+#[test]
+fn rejects_unknown_kinds() {
+    let mut image = heap().serialize();
+    image[12] = 7;
+    assert_eq!(restore_err(&image), RestoreError::UnknownKind(7));
+}
+
+#[test]
+fn rejects_truncated_images() {
+    let sa = heap();
+    for image in [sa.serialize(), sa.serialize_metadata()] {
+        for len in [0, 12, MEM_LEN_AT + 2, image.len() - 1] {
+            assert_eq!(restore_err(&image[..len]), RestoreError::Truncated);
+        }
+    }
+}
+
+#[test]
+fn rejects_trailing_bytes() {
+    let sa = heap();
+    for mut image in [sa.serialize(), sa.serialize_metadata()] {
+        image.push(0);
+        assert_eq!(restore_err(&image), RestoreError::TrailingBytes);
+    }
+}
+
+#[test]
+fn rejects_heaps_too_small_for_a_block() {
+    let sa = heap();
+    let full = sa.serialize();
+    let mem_at = full.len() - word(&full, MEM_LEN_AT) as usize;
+    for mem_len in [8, 16, 40] {
+        let mut image = full[..mem_at].to_vec();
+        put_word(&mut image, MEM_LEN_AT, mem_len);
+        image.resize(mem_at + mem_len as usize, 0);
+        assert!(matches!(restore_err(&image), RestoreError::Malformed(_)));
+
+        let mut image = sa.serialize_metadata();
+        put_word(&mut image, MEM_LEN_AT, mem_len);
+        assert!(matches!(restore_err(&image), RestoreError::Malformed(_)));
+    }
+}
+
+#[test]
+fn rejects_a_corrupt_block_walk() {
+    let image = heap().serialize_metadata();
+    assert!(word(&image, BLOCK_COUNT_AT) > 1);
+    let first = word(&image, BLOCK_COUNT_AT + 4);
+
+    let mut overrun = image.clone();
+    put_word(&mut overrun, BLOCK_COUNT_AT + 4, first + 4096);
+    assert_eq!(
+        restore_err(&overrun),
+        RestoreError::Malformed("blocks overrun heap")
+    );
+
+    let mut short = image.clone();
+    put_word(&mut short, BLOCK_COUNT_AT + 4, first - 8);
+    assert_eq!(
+        restore_err(&short),
+        RestoreError::Malformed("blocks do not cover heap")
+    );
+
+    // a claimed 4 GiB heap fails the walk before anything is allocated
+    let mut huge = image;
+    put_word(&mut huge, MEM_LEN_AT, Word::MAX & !7);
+    assert_eq!(
+        restore_err(&huge),
+        RestoreError::Malformed("blocks do not cover heap")
+    );
+}
+
+#[test]
+fn rejects_a_corrupt_full_image() {
+    let mut image = heap().serialize();
+    let mem_at = image.len() - word(&image, MEM_LEN_AT) as usize;
+    // the first block's head claims a different size than its tail
+    let head = word(&image[mem_at..], 0);
+    image[mem_at..mem_at + 4].copy_from_slice(&(head + 8).to_ne_bytes());
+    assert!(matches!(restore_err(&image), RestoreError::Corrupt(_)));
+}
+
+#[cfg(feature = "guards")]
+#[test]
+fn metadata_restore_rearms_guards() {
+    let sa = heap();
+    let mut restored = SubAllocator::from_metadata(&sa.metadata()).unwrap();
+    restored.check_guards().unwrap();
+    let addr = restored.used_blocks().next().unwrap().offset;
+    let len = restored.payload(addr).unwrap().len();
+    restored.payload_mut(addr).unwrap().fill(1);
+    assert_eq!(len, 128);
+    restored.deallocate(addr).unwrap();
+    assert_eq!(restored.free(), restored.capacity());
+}
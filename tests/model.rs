@@ -0,0 +1,167 @@
+//! Differential test: random operation sequences run against `SubAllocator`
+//! and a reference model, a sorted map of the live payload intervals.
+
+use proptest::prelude::*;
+use proptest::sample::Index;
+use std::collections::BTreeMap;
+use suballoc::{AllocHint, Storage, SubAllocator, Word};
+
+// block head and tail around every payload
+const BLOCK_META_SIZE: Word = 16;
+
+#[derive(Debug, Clone)]
+enum Op {
+    Allocate(Word),
+    AllocateHigh(Word),
+    Deallocate(Index),
+    Reset,
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        4 => (1..512u32).prop_map(Op::Allocate),
+        1 => (1..4096u32).prop_map(Op::Allocate),
+        2 => (1..512u32).prop_map(Op::AllocateHigh),
+        5 => any::<Index>().prop_map(Op::Deallocate),
+        1 => Just(Op::Reset),
+    ]
+}
+
+struct Live {
+    requested: Word,
+    end: usize,
+    fill: u8,
+}
+
+#[derive(Default)]
+struct Model {
+    /// payload start to the live allocation there
+    live: BTreeMap<usize, Live>,
+    /// block offset as returned by `allocate` to payload start
+    addrs: BTreeMap<Word, usize>,
+}
+
+impl Model {
+    fn insert(&mut self, sa: &SubAllocator, addr: Word, requested: Word, fill: u8) {
+        let base = sa.storage().mem_ptr() as usize;
+        let payload = sa.payload(addr).unwrap().as_ptr_range();
+        let (start, end) = (payload.start as usize - base, payload.end as usize - base);
+        assert!(end - start >= requested as usize, "short payload at {addr}");
+        assert!(
+            end <= sa.storage().mem_len() as usize,
+            "{addr} out of bounds"
+        );
+        if let Some((_, prev)) = self.live.range(..end).next_back() {
+            assert!(prev.end <= start, "{addr} overlaps a live allocation");
+        }
+        self.live.insert(
+            start,
+            Live {
+                requested,
+                end,
+                fill,
+            },
+        );
+        self.addrs.insert(addr, start);
+    }
+
+    fn remove(&mut self, addr: Word) -> Live {
+        let start = self.addrs.remove(&addr).unwrap();
+        self.live.remove(&start).unwrap()
+    }
+}
+
+fn allocate(sa: &mut SubAllocator, model: &mut Model, size: Word, hint: AllocHint) {
+    let before = sa.used_blocks().collect::<Vec<_>>();
+    match sa.allocate_with_hint(size, hint) {
+        Ok(addr) => {
+            let fill = addr as u8 | 1;
+            sa.payload_mut(addr).unwrap().fill(fill);
+            model.insert(sa, addr, size, fill);
+        }
+        Err(_) => assert_eq!(sa.used_blocks().collect::<Vec<_>>(), before),
+    }
+}
+
+fn check(sa: &SubAllocator, model: &Model) {
+    sa.check_integrity().unwrap();
+    let used = sa.used_blocks().collect::<Vec<_>>();
+    assert_eq!(
+        used.iter().map(|b| b.offset).collect::<Vec<_>>(),
+        model.addrs.keys().copied().collect::<Vec<_>>()
+    );
+    for block in &used {
+        let live = &model.live[&model.addrs[&block.offset]];
+        assert!(block.size >= live.requested);
+        let payload = sa.payload(block.offset).unwrap();
+        assert!(
+            payload.iter().all(|&b| b == live.fill),
+            "{block:?} clobbered"
+        );
+    }
+
+    // every byte is either in a used block, a free block or block metadata
+    let used_total: Word = used.iter().map(|b| b.size + BLOCK_META_SIZE).sum();
+    let free_count = sa.free_blocks().count() as Word;
+    let free_total: Word = sa.free_blocks().map(|b| b.size).sum();
+    assert_eq!(sa.free(), free_total);
+    assert_eq!(
+        used_total + sa.free() + free_count * BLOCK_META_SIZE,
+        sa.storage().mem_len()
+    );
+}
+
+fn run(capacity: Word, ops: &[Op]) {
+    let mut sa = SubAllocator::new(capacity);
+    let mut model = Model::default();
+    for op in ops {
+        match op {
+            Op::Allocate(size) => allocate(&mut sa, &mut model, *size, AllocHint::Low),
+            Op::AllocateHigh(size) => allocate(&mut sa, &mut model, *size, AllocHint::High),
+            Op::Deallocate(index) => {
+                if model.addrs.is_empty() {
+                    continue;
+                }
+                let addr = *model
+                    .addrs
+                    .keys()
+                    .nth(index.index(model.addrs.len()))
+                    .unwrap();
+                let live = model.remove(addr);
+                let payload = sa.payload(addr).unwrap();
+                assert!(payload.iter().all(|&b| b == live.fill));
+                sa.deallocate(addr).unwrap();
+            }
+            Op::Reset => {
+                sa.reset();
+                model = Model::default();
+            }
+        }
+        check(&sa, &model);
+    }
+
+    for addr in model.addrs.keys().copied().collect::<Vec<_>>() {
+        sa.deallocate(addr).unwrap();
+    }
+    assert_eq!(sa.free(), sa.capacity());
+}
+
+proptest! {
+    #![proptest_config(match cfg!(miri) {
+        // Miri has no file system access for the regression file
+        true => ProptestConfig {
+            cases: 4,
+            failure_persistence: None,
+            ..ProptestConfig::default()
+        },
+        false => ProptestConfig::with_cases(512),
+    })]
+
+    #[test]
+    fn matches_model(
+        capacity in (8..2048u32).prop_map(|words| words * 8),
+        ops in prop::collection::vec(op(), 1..if cfg!(miri) { 50 } else { 200 }),
+    ) {
+        run(capacity, &ops);
+    }
+}
@@ -0,0 +1,123 @@
+use suballoc::{AllocObserver, BlockInfo, OwnedStorage, SubAllocator, Word};
+
+#[derive(Debug, PartialEq, Eq)]
+enum Event {
+    Allocate(Word, Word),
+    Deallocate(Word, Word),
+    Split(Word, Word),
+    Coalesce(Word, Word),
+    Oom(Word),
+}
+
+#[derive(Default)]
+struct Recorder(Vec<Event>);
+
+impl AllocObserver for Recorder {
+    fn on_allocate(&mut self, offset: Word, size: Word) {
+        self.0.push(Event::Allocate(offset, size));
+    }
+
+    fn on_deallocate(&mut self, offset: Word, size: Word) {
+        self.0.push(Event::Deallocate(offset, size));
+    }
+
+    fn on_split(&mut self, offset: Word, size: Word) {
+        self.0.push(Event::Split(offset, size));
+    }
+
+    fn on_coalesce(&mut self, offset: Word, size: Word) {
+        self.0.push(Event::Coalesce(offset, size));
+    }
+
+    fn on_oom(&mut self, size: Word) {
+        self.0.push(Event::Oom(size));
+    }
+}
+
+fn block_at(sa: &SubAllocator<OwnedStorage, Recorder>, offset: Word) -> BlockInfo {
+    sa.blocks().find(|b| b.offset == offset).unwrap()
+}
+
+fn take(sa: &mut SubAllocator<OwnedStorage, Recorder>) -> Vec<Event> {
+    std::mem::take(&mut sa.observer_mut().0)
+}
+
+#[test]
+fn reports_each_event_in_order() {
+    let mut sa = SubAllocator::with_observer(OwnedStorage::new(1024), Recorder::default());
+
+    let a = sa.allocate(16).unwrap();
+    let a_block = block_at(&sa, a);
+    let rest = block_at(&sa, a_block.end());
+    assert_eq!(
+        take(&mut sa),
+        [
+            Event::Split(rest.offset, rest.size),
+            Event::Allocate(a, a_block.size)
+        ]
+    );
+
+    let b = sa.allocate(16).unwrap();
+    let b_block = block_at(&sa, b);
+    let rest = block_at(&sa, b_block.end());
+    assert_eq!(
+        take(&mut sa),
+        [
+            Event::Split(rest.offset, rest.size),
+            Event::Allocate(b, b_block.size)
+        ]
+    );
+
+    sa.deallocate(a).unwrap();
+    assert_eq!(take(&mut sa), [Event::Deallocate(a, a_block.size)]);
+
+    sa.deallocate(b).unwrap();
+    assert_eq!(
+        take(&mut sa),
+        [
+            Event::Deallocate(b, b_block.size),
+            Event::Coalesce(rest.offset, rest.size),
+            Event::Coalesce(a, a_block.size)
+        ]
+    );
+    assert_eq!(sa.free(), sa.capacity());
+
+    assert!(sa.allocate(4096).is_err());
+    assert_eq!(take(&mut sa), [Event::Oom(4096)]);
+}
+
+#[test]
+fn batch_calls_report_every_block() {
+    let mut sa = SubAllocator::with_observer(OwnedStorage::new(4096), Recorder::default());
+    let mut addrs = [0; 4];
+    sa.allocate_many(32, 4, &mut addrs).unwrap();
+    let allocated = take(&mut sa)
+        .into_iter()
+        .filter_map(|e| match e {
+            Event::Allocate(offset, _) => Some(offset),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(allocated, addrs);
+
+    sa.deallocate_many(&addrs).unwrap();
+    let freed = take(&mut sa)
+        .into_iter()
+        .filter(|e| matches!(e, Event::Deallocate(..)))
+        .count();
+    assert_eq!(freed, addrs.len());
+}
+
+#[test]
+fn failed_batch_reports_nothing_freed() {
+    let mut sa = SubAllocator::with_observer(OwnedStorage::new(1024), Recorder::default());
+    let mut addrs = [0; 20];
+    assert!(sa.allocate_many(100, 20, &mut addrs).is_err());
+    let events = take(&mut sa);
+    assert!(
+        !events
+            .iter()
+            .any(|e| matches!(e, Event::Allocate(..) | Event::Deallocate(..)))
+    );
+    assert_eq!(sa.free(), sa.capacity());
+}
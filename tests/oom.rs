@@ -0,0 +1,84 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use suballoc::{AllocError, OOM_RETRY_LIMIT, OomAction, SubAllocator, Word};
+
+fn fill(sa: &mut SubAllocator, size: Word) -> Vec<Word> {
+    let mut addrs = Vec::new();
+    while let Ok(addr) = sa.allocate(size) {
+        addrs.push(addr);
+    }
+    addrs
+}
+
+#[test]
+fn handler_evicts_cached_blocks_and_retries() {
+    let mut sa = SubAllocator::new(4096);
+    let cache = Rc::new(RefCell::new(fill(&mut sa, 256)));
+    let requests = Rc::new(RefCell::new(Vec::new()));
+
+    let (evictable, seen) = (cache.clone(), requests.clone());
+    sa.on_out_of_memory(move |sa, request| {
+        seen.borrow_mut().push(request);
+        match evictable.borrow_mut().pop() {
+            Some(addr) => {
+                sa.deallocate(addr).unwrap();
+                OomAction::Retry
+            }
+            None => OomAction::Fail,
+        }
+    });
+
+    let big = sa.allocate(400).unwrap();
+    let requests = requests.borrow();
+    assert!(!requests.is_empty());
+    for (i, request) in requests.iter().enumerate() {
+        assert_eq!((request.size, request.attempt), (400, i as u32));
+        assert_eq!(
+            request.stats.used_blocks,
+            cache.borrow().len() + requests.len() - i
+        );
+    }
+    assert!(sa.payload(big).unwrap().len() >= 400);
+    sa.check_integrity().unwrap();
+}
+
+#[test]
+fn retries_are_bounded() {
+    let mut sa = SubAllocator::new(1024);
+    let calls = Rc::new(RefCell::new(0));
+    let counter = calls.clone();
+    sa.on_out_of_memory(move |_, _| {
+        *counter.borrow_mut() += 1;
+        OomAction::Retry
+    });
+    assert!(matches!(sa.allocate(4096), Err(AllocError::OutOfMemory)));
+    assert_eq!(*calls.borrow(), OOM_RETRY_LIMIT);
+
+    // still installed for the next allocation
+    assert!(sa.allocate(4096).is_err());
+    assert_eq!(*calls.borrow(), 2 * OOM_RETRY_LIMIT);
+}
+
+#[test]
+fn handler_allocating_does_not_recurse() {
+    let mut sa = SubAllocator::new(1024);
+    let live = fill(&mut sa, 64);
+    let depth = Rc::new(RefCell::new(0));
+    let tracker = depth.clone();
+    sa.on_out_of_memory(move |sa, _| {
+        *tracker.borrow_mut() += 1;
+        // no handler while this one runs, so this fails straight away
+        assert!(sa.allocate(64).is_err());
+        OomAction::Fail
+    });
+    assert!(sa.allocate(64).is_err());
+    assert_eq!(*depth.borrow(), 1);
+
+    sa.clear_oom_handler();
+    assert!(sa.allocate(64).is_err());
+    assert_eq!(*depth.borrow(), 1);
+    for addr in live {
+        sa.deallocate(addr).unwrap();
+    }
+    assert_eq!(sa.free(), sa.capacity());
+}
@@ -0,0 +1,122 @@
+use suballoc::{AllocHint, OOM_RETRY_LIMIT, OomAction, OpCounts, SubAllocator, Word};
+
+struct XorShift(u64);
+
+impl XorShift {
+    fn below(&mut self, n: u64) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 % n
+    }
+}
+
+fn max(a: OpCounts, b: OpCounts) -> OpCounts {
+    OpCounts {
+        bitmap_scans: a.bitmap_scans.max(b.bitmap_scans),
+        link_updates: a.link_updates.max(b.link_updates),
+        coalesces: a.coalesces.max(b.coalesces),
+    }
+}
+
+#[test]
+fn random_calls_stay_within_bound_at_any_heap_size() {
+    let steps = if cfg!(miri) { 300 } else { 20_000 };
+    for mem_len in [1 << 12, 1 << 16, 1 << 22] {
+        let mut rng = XorShift(0x0b0b_0000 ^ mem_len as u64);
+        let mut sa = SubAllocator::new(mem_len);
+        sa.take_op_counts();
+        let (mut worst_alloc, mut worst_free) = (OpCounts::default(), OpCounts::default());
+        let mut live = Vec::new();
+        for _ in 0..steps {
+            match rng.below(5) < 3 {
+                true => {
+                    let bits = rng.below(14);
+                    let size = 1 + rng.below(1 << bits) as Word;
+                    let hint = match rng.below(2) {
+                        0 => AllocHint::Low,
+                        _ => AllocHint::High,
+                    };
+                    if let Ok(addr) = sa.allocate_with_hint(size, hint) {
+                        live.push(addr);
+                    }
+                    let counts = sa.take_op_counts();
+                    assert!(counts.within(&OpCounts::MAX_ALLOCATE), "{counts:?}");
+                    worst_alloc = max(worst_alloc, counts);
+                }
+                false if !live.is_empty() => {
+                    let addr = live.swap_remove(rng.below(live.len() as u64) as usize);
+                    sa.deallocate(addr).unwrap();
+                    let counts = sa.take_op_counts();
+                    assert!(counts.within(&OpCounts::MAX_DEALLOCATE), "{counts:?}");
+                    worst_free = max(worst_free, counts);
+                }
+                false => {}
+            }
+            assert_eq!(sa.free(), sa.free_blocks().map(|b| b.size).sum::<Word>());
+        }
+        // the bounds are tight, given enough steps to hit the worst case
+        if !cfg!(miri) {
+            assert_eq!(worst_alloc, OpCounts::MAX_ALLOCATE);
+            assert_eq!(worst_free, OpCounts::MAX_DEALLOCATE);
+        }
+    }
+}
+
+#[test]
+fn fragmented_heap_stays_within_bound() {
+    let mut sa = SubAllocator::new(1 << 16);
+    let mut addrs = Vec::new();
+    while let Ok(addr) = sa.allocate(40) {
+        addrs.push(addr);
+    }
+    // every other block free: long lists in a single bin
+    for &addr in addrs.iter().step_by(2) {
+        sa.deallocate(addr).unwrap();
+    }
+    sa.take_op_counts();
+
+    // the search has to skip every populated bin below the request
+    let big = sa.allocate(1024);
+    assert!(big.is_err());
+    assert!(sa.take_op_counts().within(&OpCounts::MAX_ALLOCATE));
+
+    // each free merges with both neighbours, unlinking them mid list
+    for &addr in addrs.iter().skip(1).step_by(2) {
+        sa.deallocate(addr).unwrap();
+        let counts = sa.take_op_counts();
+        assert!(counts.within(&OpCounts::MAX_DEALLOCATE), "{counts:?}");
+    }
+    assert_eq!(sa.free(), sa.capacity());
+}
+
+#[test]
+fn oom_handler_retries_scale_the_bound() {
+    let mut sa = SubAllocator::new(1 << 12);
+    let mut addrs = Vec::new();
+    while let Ok(addr) = sa.allocate(40) {
+        addrs.push(addr);
+    }
+    for &addr in addrs.iter().step_by(2) {
+        sa.deallocate(addr).unwrap();
+    }
+    sa.on_out_of_memory(|_, _| OomAction::Fail);
+    sa.take_op_counts();
+    assert!(sa.allocate(1024).is_err());
+    let search = sa.take_op_counts();
+    assert_ne!(search.bitmap_scans, 0);
+    assert!(search.within(&OpCounts::MAX_ALLOCATE), "{search:?}");
+
+    // a handler that never frees anything: one search per attempt
+    sa.on_out_of_memory(|_, _| OomAction::Retry);
+    assert!(sa.allocate(1024).is_err());
+    let searches = OOM_RETRY_LIMIT + 1;
+    let counts = sa.take_op_counts();
+    assert_eq!(counts.bitmap_scans, search.bitmap_scans * searches);
+    let bound = OpCounts {
+        bitmap_scans: OpCounts::MAX_ALLOCATE.bitmap_scans * searches,
+        link_updates: OpCounts::MAX_ALLOCATE.link_updates * searches,
+        coalesces: 0,
+    };
+    assert!(counts.within(&bound), "{counts:?}");
+}
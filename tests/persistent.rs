@@ -0,0 +1,136 @@
+// Miri cannot map files
+#![cfg(all(unix, not(miri)))]
+
+use std::fs::OpenOptions;
+use std::io::{ErrorKind, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use suballoc::{HEAP_FILE_OFFSET, SubAllocator};
+
+// byte offsets inside the file header
+const DIRTY_FLAG: u64 = 12;
+const MEM_LEN: u64 = 16;
+const CONTROL: u64 = 24;
+
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("suballoc-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        Self(path)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+fn patch_file(path: &PathBuf, at: u64, bytes: &[u8]) {
+    let mut file = OpenOptions::new().write(true).open(path).unwrap();
+    file.seek(SeekFrom::Start(at)).unwrap();
+    file.write_all(bytes).unwrap();
+}
+
+#[test]
+fn reopen_continues_where_it_left_off() {
+    let tmp = TempFile::new("reopen");
+    let (a, b, layout) = {
+        let mut sa = SubAllocator::create_file(&tmp.0, 4096).unwrap();
+        let a = sa.allocate(100).unwrap();
+        let b = sa.allocate(24).unwrap();
+        sa.payload_mut(a).unwrap()[..5].copy_from_slice(b"hello");
+        let scratch = sa.allocate(300).unwrap();
+        sa.deallocate(scratch).unwrap();
+        (a, b, sa.metadata())
+    };
+
+    let mut sa = SubAllocator::open_file(&tmp.0).unwrap();
+    assert!(!sa.storage().recovered());
+    assert_eq!(sa.metadata(), layout);
+    assert_eq!(&sa.payload(a).unwrap()[..5], b"hello");
+
+    let c = sa.allocate(64).unwrap();
+    assert!(c != a && c != b);
+    sa.deallocate(b).unwrap();
+    sa.check_integrity().unwrap();
+}
+
+#[test]
+fn dirty_file_is_rebuilt_from_walk() {
+    let tmp = TempFile::new("dirty");
+    let live = {
+        let mut sa = SubAllocator::create_file(&tmp.0, 8192).unwrap();
+        let blocks: Vec<_> = (1..20).map(|i| sa.allocate(i * 16).unwrap()).collect();
+        for &addr in blocks.iter().step_by(3) {
+            sa.deallocate(addr).unwrap();
+        }
+        sa.used_blocks().collect::<Vec<_>>()
+    };
+
+    // an interrupted update: dirty flag raised and the control block lost
+    patch_file(&tmp.0, DIRTY_FLAG, &1u32.to_ne_bytes());
+    patch_file(&tmp.0, CONTROL, &[0xff; 64]);
+
+    let mut sa = SubAllocator::open_file(&tmp.0).unwrap();
+    assert!(sa.storage().recovered());
+    assert_eq!(sa.used_blocks().collect::<Vec<_>>(), live);
+    sa.check_integrity().unwrap();
+
+    let free_before = sa.free();
+    let addr = sa.allocate(512).unwrap();
+    sa.deallocate(addr).unwrap();
+    assert_eq!(sa.free(), free_before);
+}
+
+#[test]
+fn rejects_foreign_and_corrupt_files() {
+    let tmp = TempFile::new("foreign");
+    std::fs::write(&tmp.0, vec![0u8; HEAP_FILE_OFFSET + 64]).unwrap();
+    assert!(SubAllocator::open_file(&tmp.0).is_err());
+    std::fs::remove_file(&tmp.0).unwrap();
+
+    drop(SubAllocator::create_file(&tmp.0, 1024).unwrap());
+    patch_file(&tmp.0, HEAP_FILE_OFFSET as u64, &[0x07, 0, 0, 0]);
+    assert!(SubAllocator::open_file(&tmp.0).is_err());
+}
+
+#[test]
+fn rejects_invalid_heap_lengths() {
+    let tmp = TempFile::new("lengths");
+    for mem_len in [0u32, 16, 1020] {
+        drop(SubAllocator::create_file(&tmp.0, 1024).unwrap());
+        let file = OpenOptions::new().write(true).open(&tmp.0).unwrap();
+        file.set_len(HEAP_FILE_OFFSET as u64 + mem_len as u64)
+            .unwrap();
+        patch_file(&tmp.0, MEM_LEN, &mem_len.to_ne_bytes());
+        let err = SubAllocator::open_file(&tmp.0).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        std::fs::remove_file(&tmp.0).unwrap();
+    }
+}
+
+#[test]
+fn rejects_files_beyond_word_offsets() {
+    let tmp = TempFile::new("huge");
+    drop(SubAllocator::create_file(&tmp.0, 1024).unwrap());
+    // sparse, and refused before it is mapped
+    let file = OpenOptions::new().write(true).open(&tmp.0).unwrap();
+    file.set_len(HEAP_FILE_OFFSET as u64 + (1 << 32) + 1024)
+        .unwrap();
+    let err = SubAllocator::open_file(&tmp.0).err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+}
+
+#[test]
+fn interior_addresses_are_not_blocks() {
+    let tmp = TempFile::new("interior");
+    let mut sa = SubAllocator::create_file(&tmp.0, 1024).unwrap();
+    let addr = sa.allocate(64).unwrap();
+    sa.payload_mut(addr).unwrap().fill(0xff);
+    for interior in [addr + 8, addr + 16, addr + 56] {
+        assert!(sa.payload(interior).is_err());
+    }
+    assert!(sa.payload(!7).is_err());
+}
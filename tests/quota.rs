@@ -0,0 +1,139 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use suballoc::{AllocError, AllocTag, QuotaWarning, SubAllocator, TagUsage};
+
+// tags are charged the usable size of the block, guard bytes included
+fn block_size(size: u32) -> u64 {
+    let mut sa = SubAllocator::new(1024);
+    sa.allocate(size).unwrap();
+    sa.used_blocks().next().unwrap().size as u64
+}
+
+#[test]
+fn hard_limit_fails_the_allocation() {
+    let mut sa = SubAllocator::new(4096);
+    sa.set_quota("net", 100);
+    let a = sa.allocate_tagged(64, "net").unwrap();
+    match sa.allocate_tagged(33, "net") {
+        Err(AllocError::QuotaExceeded { tag, limit, used }) => {
+            assert_eq!(
+                (tag, limit, used),
+                (AllocTag::Name("net"), 100, block_size(64))
+            )
+        }
+        res => panic!("{res:?}"),
+    }
+    // nothing was allocated or charged for the failed call
+    assert_eq!(sa.used_blocks().count(), 1);
+    assert_eq!(sa.tag_usage("net").used, block_size(64));
+
+    // other tags and untagged allocations are not limited
+    sa.allocate_tagged(512, "img").unwrap();
+    sa.allocate(512).unwrap();
+    sa.deallocate(a).unwrap();
+    sa.allocate_tagged(88, "net").unwrap();
+
+    sa.clear_quota("net");
+    sa.allocate_tagged(512, "net").unwrap();
+}
+
+#[test]
+fn soft_limit_warns_once_per_crossing() {
+    let mut sa = SubAllocator::new(4096);
+    let warnings = Rc::new(RefCell::new(Vec::new()));
+    let seen = warnings.clone();
+    sa.on_soft_quota(move |warning| seen.borrow_mut().push(warning));
+    sa.set_soft_quota(7, 100);
+
+    let a = sa.allocate_tagged(64, 7).unwrap();
+    assert!(warnings.borrow().is_empty());
+    let b = sa.allocate_tagged(64, 7).unwrap();
+    let c = sa.allocate_tagged(64, 7).unwrap();
+    let expected = QuotaWarning {
+        tag: AllocTag::Id(7),
+        soft_limit: 100,
+        used: 2 * block_size(64),
+    };
+    assert_eq!(*warnings.borrow(), [expected]);
+
+    // back below the limit, crossing it again warns again
+    sa.deallocate(b).unwrap();
+    sa.deallocate(c).unwrap();
+    sa.allocate_tagged(64, 7).unwrap();
+    assert_eq!(warnings.borrow().len(), 2);
+    sa.deallocate(a).unwrap();
+}
+
+#[test]
+fn usage_follows_allocations() {
+    let mut sa = SubAllocator::new(4096);
+    let (small, large) = (block_size(10), block_size(100));
+    let a = sa.allocate_tagged(10, "ui").unwrap();
+    let b = sa.allocate_tagged(100, "ui").unwrap();
+    assert_eq!(
+        sa.tag_usage("ui"),
+        TagUsage {
+            used: small + large,
+            high_water: small + large,
+            count: 2
+        }
+    );
+
+    sa.deallocate(b).unwrap();
+    let usage = sa.tag_usage("ui");
+    assert_eq!(
+        (usage.used, usage.high_water, usage.count),
+        (small, small + large, 1)
+    );
+    sa.deallocate(a).unwrap();
+    assert_eq!(sa.tag_usage("ui").used, 0);
+
+    sa.allocate_tagged(200, "ui").unwrap();
+    assert_eq!(sa.tag_usage("ui").high_water, block_size(200));
+    assert_eq!(
+        sa.tag_usages().map(|(tag, _)| tag).collect::<Vec<_>>(),
+        [AllocTag::Name("ui")]
+    );
+}
+
+#[test]
+fn reset_drops_usage_and_keeps_high_water() {
+    let mut sa = SubAllocator::new(4096);
+    sa.set_quota("net", block_size(256));
+    sa.allocate_tagged(200, "net").unwrap();
+    sa.reset();
+    assert_eq!(
+        sa.tag_usage("net"),
+        TagUsage {
+            used: 0,
+            high_water: block_size(200),
+            count: 0
+        }
+    );
+    // the budget survives and starts from zero again
+    sa.allocate_tagged(256, "net").unwrap();
+    assert!(sa.allocate_tagged(8, "net").is_err());
+}
+
+#[test]
+fn absorbed_leftovers_are_charged() {
+    // capacity 3840 starts a second level class, so the request below is
+    // served by the whole heap and its 16 byte leftover is absorbed
+    let mut sa = SubAllocator::new(3856);
+    let guard = block_size(8) as u32 - 8;
+    let size = 3840 - 16 - guard;
+    // enough for the block the request alone would need
+    sa.set_quota("net", (size + guard) as u64);
+    match sa.allocate_tagged(size, "net") {
+        Err(AllocError::QuotaExceeded { used: 0, .. }) => {}
+        res => panic!("{res:?}"),
+    }
+    assert_eq!(sa.free(), sa.capacity());
+    assert_eq!(sa.tag_usage("net"), TagUsage::default());
+
+    sa.clear_quota("net");
+    let addr = sa.allocate_tagged(size, "net").unwrap();
+    assert_eq!(sa.tag_usage("net").used, sa.capacity() as u64);
+    sa.deallocate(addr).unwrap();
+    assert_eq!(sa.tag_usage("net").used, 0);
+}
@@ -0,0 +1,74 @@
+use suballoc::{RenderOptions, SubAllocator};
+
+fn heap() -> SubAllocator {
+    let mut sa = SubAllocator::new(4096);
+    sa.allocate_tagged(1024, "<mesh>").unwrap();
+    sa.allocate(1024).unwrap();
+    sa
+}
+
+#[test]
+fn ascii_bar_and_block_list() {
+    let sa = heap();
+    let out = sa.render_ascii(4);
+    let lines = out.lines().collect::<Vec<_>>();
+    assert_eq!(lines[0], "[##+.]");
+    assert_eq!(lines.len(), 2 + sa.blocks().count());
+    for (line, block) in lines[2..].iter().zip(sa.blocks()) {
+        let fields = line.split_whitespace().collect::<Vec<_>>();
+        assert_eq!(fields[0], block.offset.to_string());
+        assert_eq!(fields[1], block.size.to_string());
+        assert_eq!(fields[2], if block.used { "used" } else { "free" });
+    }
+    assert!(lines[2].ends_with("<mesh>"));
+    // a zero width still draws one cell
+    assert!(sa.render_ascii(0).starts_with("[+]\n"));
+}
+
+#[test]
+fn svg_has_a_rect_per_block() {
+    let sa = heap();
+    let svg = sa.render_svg(&RenderOptions::default());
+    assert!(svg.starts_with("<svg") && svg.ends_with("</svg>"));
+    assert_eq!(svg.matches("<rect").count(), sa.blocks().count());
+    assert!(svg.contains("&lt;mesh&gt;"));
+    assert!(!svg.contains("<mesh>"));
+
+    let labels = |offset| (offset == 0).then(|| "first".to_string());
+    let options = RenderOptions {
+        labels: Some(&labels),
+        ..RenderOptions::default()
+    };
+    let svg = sa.render_svg(&options);
+    assert!(svg.contains(", first") && !svg.contains("mesh"));
+}
+
+#[test]
+fn heatmap_counts_free_bins() {
+    let mut sa = SubAllocator::new(4096);
+    let addrs = (0..6).map(|_| sa.allocate(64).unwrap()).collect::<Vec<_>>();
+    for &addr in addrs.iter().step_by(2) {
+        sa.deallocate(addr).unwrap();
+    }
+    let svg = sa.render_bin_heatmap_svg();
+    for (fli, sli, list) in sa.free_bins() {
+        let cell = format!("fl {fli}, sl {sli}: {} free", list.count());
+        assert!(svg.contains(&cell), "{cell}");
+    }
+    let bins = sa.free_bins().count();
+    assert_eq!(
+        svg.matches(": 0 free").count() + bins,
+        svg.matches("<rect").count()
+    );
+}
+
+#[test]
+fn html_embeds_every_view() {
+    let sa = heap();
+    let options = RenderOptions::default();
+    let html = sa.render_html(&options);
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains(&sa.render_svg(&options)));
+    assert!(html.contains(&sa.render_bin_heatmap_svg()));
+    assert!(html.contains("&lt;mesh&gt;"));
+}
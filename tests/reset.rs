@@ -0,0 +1,36 @@
+use suballoc::{AllocError, SubAllocator};
+
+#[test]
+fn reset_drops_tags_usage_and_checkpoints() {
+    let mut sa = SubAllocator::new(4096);
+    sa.track_allocations(true);
+    sa.set_quota("net", 512);
+    let mark = sa.checkpoint();
+    let a = sa.allocate_tagged(200, "net").unwrap();
+    sa.allocate(64).unwrap();
+
+    sa.reset();
+    assert_eq!(sa.free(), sa.capacity());
+    assert_eq!(sa.blocks().count(), 1);
+    assert_eq!(sa.tag_of(a), None);
+    assert_eq!(sa.leak_report().count, 0);
+    assert_eq!(sa.tag_usage("net").used, 0);
+    assert_eq!(sa.tag_usage("net").count, 0);
+    assert!(matches!(
+        sa.rollback(mark),
+        Err(AllocError::InvalidAllocation)
+    ));
+    assert!(matches!(
+        sa.commit(mark),
+        Err(AllocError::InvalidAllocation)
+    ));
+
+    // a checkpoint taken afterwards only sees the new allocations
+    let mark = sa.checkpoint();
+    let b = sa.allocate_tagged(300, "net").unwrap();
+    assert_eq!(b, a);
+    sa.rollback(mark).unwrap();
+    assert_eq!(sa.free(), sa.capacity());
+    assert_eq!(sa.tag_usage("net").used, 0);
+    sa.check_integrity().unwrap();
+}
@@ -0,0 +1,170 @@
+use suballoc::{AllocError, FreeOrder, OwnedStorage, RingAllocator};
+
+#[test]
+fn in_order_frees_wrap_around_the_tail_gap() {
+    let mut ring = RingAllocator::new(256);
+    let addrs: Vec<_> = (0..3).map(|_| ring.allocate(64).unwrap()).collect();
+    assert_eq!(addrs, [0, 72, 144]);
+
+    // 40 bytes left at the end are too short, so it wraps into the freed front
+    ring.deallocate(addrs[0]).unwrap();
+    let wrapped = ring.allocate(64).unwrap();
+    assert_eq!(wrapped, 0);
+    assert!(matches!(ring.allocate(8), Err(AllocError::OutOfMemory)));
+
+    ring.payload_mut(wrapped).unwrap().fill(0xab);
+    ring.deallocate(addrs[1]).unwrap();
+    ring.deallocate(addrs[2]).unwrap();
+    // the gap went with the last block before it
+    let stats = ring.stats();
+    assert_eq!((stats.used, stats.used_blocks), (64, 1));
+    assert_eq!(ring.payload(wrapped).unwrap(), [0xab; 64]);
+
+    ring.deallocate(wrapped).unwrap();
+    assert_eq!(ring.stats().free, ring.capacity());
+    assert_eq!(ring.allocate(ring.capacity()).unwrap(), 0);
+}
+
+#[test]
+fn strict_order_rejects_newer_blocks() {
+    let mut ring = RingAllocator::new(256);
+    let first = ring.allocate(16).unwrap();
+    let second = ring.allocate(16).unwrap();
+    assert!(matches!(
+        ring.deallocate(second),
+        Err(AllocError::OutOfOrderFree { offset, oldest }) if offset == second && oldest == first
+    ));
+    assert_eq!(ring.stats().used_blocks, 2);
+
+    ring.deallocate(first).unwrap();
+    ring.deallocate(second).unwrap();
+    assert!(matches!(
+        ring.deallocate(second),
+        Err(AllocError::InvalidAllocation)
+    ));
+}
+
+#[test]
+fn deferred_frees_are_reclaimed_once_in_order() {
+    let mut ring = RingAllocator::new(256);
+    ring.set_free_order(FreeOrder::Deferred);
+    let addrs: Vec<_> = (0..3).map(|_| ring.allocate(64).unwrap()).collect();
+
+    ring.deallocate(addrs[2]).unwrap();
+    ring.deallocate(addrs[1]).unwrap();
+    let stats = ring.stats();
+    assert_eq!((stats.used_blocks, stats.free), (1, 32));
+    assert!(ring.payload(addrs[1]).is_err());
+
+    ring.deallocate(addrs[0]).unwrap();
+    assert_eq!(ring.stats().free, ring.capacity());
+}
+
+#[test]
+fn pinned_ring_falls_back_to_tlsf() {
+    let mut ring = RingAllocator::with_fallback(OwnedStorage::new(1024), 256);
+    let pinned = ring.allocate(64).unwrap();
+    let mut streamed = Vec::new();
+    while let Ok(addr) = ring.allocate(64) {
+        streamed.push(addr);
+        if addr >= 256 {
+            break;
+        }
+    }
+    let spilled = *streamed.last().unwrap();
+    assert!(spilled >= 256);
+    ring.payload_mut(spilled).unwrap().fill(1);
+
+    let unordered = ring.allocate_unordered(32).unwrap();
+    assert!(unordered >= 256 && unordered != spilled);
+    ring.payload_mut(unordered).unwrap().fill(2);
+    assert_eq!(ring.payload(spilled).unwrap(), [1; 64]);
+    let fallback = ring.fallback().unwrap();
+    assert_eq!(fallback.stats().used_blocks, 2);
+
+    // fallback blocks go in any order, ring ones after the pinned block
+    ring.deallocate(unordered).unwrap();
+    ring.deallocate(streamed.pop().unwrap()).unwrap();
+    assert!(ring.deallocate(streamed[0]).is_err());
+    ring.deallocate(pinned).unwrap();
+    for addr in streamed {
+        ring.deallocate(addr).unwrap();
+    }
+    assert_eq!(ring.stats().free, ring.capacity());
+    let fallback = ring.fallback().unwrap();
+    assert_eq!(fallback.free(), fallback.capacity());
+}
+
+#[test]
+fn without_fallback_unordered_allocations_fail() {
+    let mut ring = RingAllocator::new(64);
+    assert!(ring.allocate_unordered(8).is_err());
+    assert!(matches!(
+        ring.deallocate(512),
+        Err(AllocError::InvalidAllocation)
+    ));
+    assert!(ring.allocate(64).is_err());
+}
+
+#[test]
+fn interior_addresses_are_not_blocks() {
+    let mut ring = RingAllocator::new(256);
+    ring.set_free_order(FreeOrder::Deferred);
+    let addr = ring.allocate(64).unwrap();
+    let next = ring.allocate(8).unwrap();
+    ring.payload_mut(addr).unwrap().fill(0xff);
+    for interior in [addr + 8, addr + 16, next - 8] {
+        assert!(matches!(
+            ring.payload(interior),
+            Err(AllocError::InvalidAllocation)
+        ));
+        assert!(matches!(
+            ring.deallocate(interior),
+            Err(AllocError::InvalidAllocation)
+        ));
+    }
+    assert_eq!(ring.payload(addr).unwrap(), [0xff; 64]);
+    assert_eq!(ring.stats().used_blocks, 2);
+}
+
+#[test]
+fn addresses_outside_the_used_span_are_not_blocks() {
+    let mut ring = RingAllocator::new(256);
+    ring.set_free_order(FreeOrder::Deferred);
+    let addrs: Vec<_> = (0..3).map(|_| ring.allocate(64).unwrap()).collect();
+    ring.deallocate(addrs[0]).unwrap();
+    // reclaimed, its header still in place
+    assert!(matches!(
+        ring.payload(addrs[0]),
+        Err(AllocError::InvalidAllocation)
+    ));
+
+    let wrapped = ring.allocate(64).unwrap();
+    assert_eq!(wrapped, 0);
+    // the skipped end of the ring
+    assert!(matches!(
+        ring.payload(216),
+        Err(AllocError::InvalidAllocation)
+    ));
+    assert!(ring.payload(wrapped).is_ok() && ring.payload(addrs[2]).is_ok());
+
+    // freed out of order but not reclaimed yet
+    ring.deallocate(addrs[2]).unwrap();
+    assert!(matches!(
+        ring.deallocate(addrs[2]),
+        Err(AllocError::InvalidAllocation)
+    ));
+
+    ring.set_free_order(FreeOrder::Strict);
+    assert!(matches!(
+        ring.deallocate(wrapped + 8),
+        Err(AllocError::InvalidAllocation)
+    ));
+    assert!(matches!(
+        ring.deallocate(wrapped),
+        Err(AllocError::OutOfOrderFree { .. })
+    ));
+    ring.deallocate(addrs[1]).unwrap();
+    ring.deallocate(wrapped).unwrap();
+    assert_eq!(ring.stats().free, ring.capacity());
+}
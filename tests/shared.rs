@@ -0,0 +1,136 @@
+// fork and cross process futexes are out of reach for Miri
+#![cfg(all(target_os = "linux", not(miri)))]
+
+use std::fs::File;
+use std::io::ErrorKind;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::fs::FileExt;
+use suballoc::{SHARED_REGION_OFFSET, SharedRegion, Storage, SubAllocator};
+
+const CHILDREN: usize = 4;
+const ROUNDS: u32 = 2000;
+
+fn churn(sa: &mut SubAllocator<SharedRegion>, id: u8) -> bool {
+    let mut seed = 0x9e37_79b9u32.wrapping_mul(id as u32 + 1);
+    let mut live = [None; 8];
+    for _ in 0..ROUNDS {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        let slot = &mut live[seed as usize % 8];
+        match slot.take() {
+            Some(addr) => {
+                if sa.payload(addr).unwrap().iter().any(|&b| b != id) {
+                    return false;
+                }
+                sa.deallocate(addr).unwrap();
+            }
+            None => {
+                let Ok(addr) = sa.allocate(8 + seed % 200) else {
+                    continue;
+                };
+                sa.payload_mut(addr).unwrap().fill(id);
+                *slot = Some(addr);
+            }
+        }
+    }
+    live.iter()
+        .flatten()
+        .all(|&addr| sa.deallocate(addr).is_ok())
+}
+
+#[test]
+fn forked_children_share_the_heap() {
+    let mut sa = SubAllocator::new_shared(1 << 16).unwrap();
+    let mailbox = sa.allocate((CHILDREN * 4) as u32).unwrap();
+
+    let pids: Vec<_> = (0..CHILDREN)
+        .map(|i| match unsafe { libc::fork() } {
+            0 => {
+                let ok = churn(&mut sa, i as u8 + 1);
+                let code = match (ok, sa.allocate(16)) {
+                    (true, Ok(addr)) => {
+                        sa.payload_mut(addr).unwrap().fill(0xc0 | i as u8);
+                        let slot = &mut sa.payload_mut(mailbox).unwrap()[i * 4..i * 4 + 4];
+                        slot.copy_from_slice(&addr.to_ne_bytes());
+                        0
+                    }
+                    _ => 1,
+                };
+                unsafe { libc::_exit(code) }
+            }
+            pid => {
+                assert!(pid > 0);
+                pid
+            }
+        })
+        .collect();
+
+    for pid in pids {
+        let mut status = 0;
+        assert_eq!(unsafe { libc::waitpid(pid, &mut status, 0) }, pid);
+        assert!(libc::WIFEXITED(status) && libc::WEXITSTATUS(status) == 0);
+    }
+
+    sa.locked(|sa| sa.check_integrity()).unwrap();
+    let slots = sa.payload(mailbox).unwrap().to_vec();
+    for (i, slot) in slots.chunks(4).enumerate() {
+        let addr = u32::from_ne_bytes(slot.try_into().unwrap());
+        assert!(
+            sa.payload(addr)
+                .unwrap()
+                .iter()
+                .all(|&b| b == 0xc0 | i as u8)
+        );
+        sa.deallocate(addr).unwrap();
+    }
+    sa.deallocate(mailbox).unwrap();
+    assert_eq!(sa.used_blocks().count(), 0);
+    assert_eq!(sa.free_blocks().count(), 1);
+}
+
+#[test]
+fn memfd_attach_sees_the_same_heap() {
+    let mut a = SubAllocator::new_shared_memfd(4096).unwrap();
+    let fd = a.storage().fd().unwrap().try_clone_to_owned().unwrap();
+    let mut b = SubAllocator::attach_shared(fd).unwrap();
+    assert_ne!(a.storage().mem_ptr(), b.storage().mem_ptr());
+
+    let addr = a.allocate(40).unwrap();
+    a.payload_mut(addr).unwrap().copy_from_slice(&[7; 40]);
+    assert_eq!(b.payload(addr).unwrap(), &[7; 40]);
+    assert_eq!(
+        b.used_blocks().map(|blk| blk.offset).collect::<Vec<_>>(),
+        [addr]
+    );
+
+    b.deallocate(addr).unwrap();
+    assert_eq!(a.used_blocks().count(), 0);
+    a.check_integrity().unwrap();
+}
+
+#[test]
+fn attach_rejects_foreign_memfd() {
+    let fd = unsafe { libc::memfd_create(c"foreign".as_ptr(), libc::MFD_CLOEXEC) };
+    assert!(fd >= 0);
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+    assert_eq!(unsafe { libc::ftruncate(fd.as_raw_fd(), 8192) }, 0);
+    assert!(SubAllocator::attach_shared(fd).is_err());
+}
+
+#[test]
+fn attach_rejects_invalid_heap_lengths() {
+    // byte offset of the heap length inside the region header
+    const MEM_LEN: u64 = 16;
+    for mem_len in [0u32, 16, 1020] {
+        let sa = SubAllocator::new_shared_memfd(1024).unwrap();
+        let fd = sa.storage().fd().unwrap().try_clone_to_owned().unwrap();
+        let file = File::from(fd.try_clone().unwrap());
+        file.set_len(SHARED_REGION_OFFSET as u64 + mem_len as u64)
+            .unwrap();
+        file.write_all_at(&mem_len.to_ne_bytes(), MEM_LEN).unwrap();
+        drop(sa);
+        let err = SubAllocator::attach_shared(fd).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+}
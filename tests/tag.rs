@@ -0,0 +1,100 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use suballoc::{AllocTag, LeakReport, SubAllocator};
+
+#[test]
+fn allocations_remember_their_tag() {
+    let mut sa = SubAllocator::new(4096);
+    let a = sa.allocate_tagged(32, "mesh").unwrap();
+    let b = sa.allocate_tagged(32, 3).unwrap();
+    let c = sa.allocate(32).unwrap();
+    assert_eq!(sa.tag_of(a), Some(AllocTag::Name("mesh")));
+    assert_eq!(sa.tag_of(b), Some(AllocTag::Id(3)));
+    assert_eq!(sa.tag_of(c), None);
+
+    sa.deallocate(a).unwrap();
+    assert_eq!(sa.tag_of(a), None);
+    assert_eq!(AllocTag::Id(3).to_string(), "#3");
+}
+
+#[test]
+fn leak_report_groups_by_tag() {
+    let mut sa = SubAllocator::new(4096);
+    sa.track_allocations(true);
+    for _ in 0..3 {
+        sa.allocate_tagged(64, "mesh").unwrap();
+    }
+    let freed = sa.allocate_tagged(64, "mesh").unwrap();
+    sa.allocate_tagged(16, "name").unwrap();
+    sa.allocate(8).unwrap();
+    sa.deallocate(freed).unwrap();
+
+    let report = sa.leak_report();
+    assert_eq!(report.count, 5);
+    let groups = report
+        .entries
+        .iter()
+        .map(|e| (e.tag, e.count))
+        .collect::<Vec<_>>();
+    // largest first
+    assert_eq!(
+        groups,
+        [
+            (Some(AllocTag::Name("mesh")), 3),
+            (Some(AllocTag::Name("name")), 1),
+            (None, 1)
+        ]
+    );
+    assert_eq!(
+        report.bytes,
+        report.entries.iter().map(|e| e.bytes).sum::<u64>()
+    );
+    assert!(report.to_string().contains("<untagged>"));
+}
+
+#[cfg(feature = "alloc-sites")]
+#[test]
+fn leak_report_records_call_sites() {
+    let mut sa = SubAllocator::new(4096);
+    sa.track_allocations(true);
+    let line = line!() + 1;
+    sa.allocate_tagged(32, "mesh").unwrap();
+    for _ in 0..2 {
+        sa.allocate(32).unwrap();
+    }
+
+    let report = sa.leak_report();
+    assert_eq!(report.entries.len(), 2);
+    let tagged = report.entries.iter().find(|e| e.tag.is_some()).unwrap();
+    let site = tagged.site.unwrap();
+    assert_eq!((site.file(), site.line()), (file!(), line));
+    // both loop iterations share one site
+    let untagged = report.entries.iter().find(|e| e.tag.is_none()).unwrap();
+    assert_eq!(untagged.count, 2);
+    assert_eq!(untagged.site.unwrap().line(), line + 2);
+}
+
+#[test]
+fn drop_hook_gets_the_leaks() {
+    let reports: Rc<RefCell<Vec<LeakReport>>> = Rc::default();
+    {
+        let mut sa = SubAllocator::new(4096);
+        let seen = reports.clone();
+        sa.on_drop_leak_report(move |report| seen.borrow_mut().push(report));
+        sa.allocate_tagged(100, "leak").unwrap();
+    }
+    let reports = reports.borrow();
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].entries[0].tag, Some(AllocTag::Name("leak")));
+
+    // no call without leaks
+    let called = Rc::new(RefCell::new(false));
+    {
+        let mut sa = SubAllocator::new(4096);
+        let flag = called.clone();
+        sa.on_drop_leak_report(move |_| *flag.borrow_mut() = true);
+        let addr = sa.allocate_tagged(100, "leak").unwrap();
+        sa.deallocate(addr).unwrap();
+    }
+    assert!(!*called.borrow());
+}
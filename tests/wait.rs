@@ -0,0 +1,128 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{Context, Poll, Wake, Waker};
+use suballoc::{AllocResult, AsyncAllocator, SubAllocator, Word};
+
+/// a waker that only records being woken, so tests poll by hand
+#[derive(Default)]
+struct Flag(AtomicBool);
+
+impl Wake for Flag {
+    fn wake(self: Arc<Self>) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+struct Task<F> {
+    future: Pin<Box<F>>,
+    woken: Arc<Flag>,
+    waker: Waker,
+}
+
+impl<F: Future<Output = AllocResult<Word>>> Task<F> {
+    fn new(future: F) -> Self {
+        let woken = Arc::new(Flag::default());
+        let waker = Waker::from(woken.clone());
+        Self {
+            future: Box::pin(future),
+            woken,
+            waker,
+        }
+    }
+
+    fn poll(&mut self) -> Poll<AllocResult<Word>> {
+        self.woken.0.store(false, Ordering::SeqCst);
+        let mut cx = Context::from_waker(&self.waker);
+        self.future.as_mut().poll(&mut cx)
+    }
+
+    fn woken(&self) -> bool {
+        self.woken.0.load(Ordering::SeqCst)
+    }
+}
+
+/// a full pool, starting with at least `count` blocks of `size` bytes. A free block
+/// only serves requests from a smaller size class than its own.
+fn full_pool(size: Word, count: usize) -> (AsyncAllocator, Vec<Word>) {
+    let pool = AsyncAllocator::new(SubAllocator::new(4096));
+    let mut addrs = Vec::new();
+    while let Ok(addr) = pool.try_allocate(size) {
+        addrs.push(addr);
+    }
+    assert!(addrs.len() >= count);
+    // and whatever is left at the end
+    while pool.try_allocate(1).is_ok() {}
+    (pool, addrs)
+}
+
+#[test]
+fn waits_until_enough_is_freed() {
+    let (pool, addrs) = full_pool(128, 4);
+    let mut task = Task::new(pool.allocate_async(100));
+    assert!(task.poll().is_pending());
+    assert_eq!(pool.waiters(), 1);
+
+    pool.deallocate(addrs[1]).unwrap();
+    assert!(task.woken());
+    let Poll::Ready(Ok(addr)) = task.poll() else {
+        panic!("not ready after a free");
+    };
+    assert_eq!(addr, addrs[1]);
+    assert_eq!(pool.waiters(), 0);
+}
+
+#[test]
+fn waiters_are_served_in_arrival_order() {
+    let (pool, addrs) = full_pool(128, 4);
+    let mut large = Task::new(pool.allocate_async(200));
+    let mut small = Task::new(pool.allocate_async(64));
+    assert!(large.poll().is_pending());
+    assert!(small.poll().is_pending());
+
+    // room for the small request only: it must not overtake the large one
+    pool.deallocate(addrs[1]).unwrap();
+    assert!(!large.woken() && !small.woken());
+    assert!(small.poll().is_pending());
+
+    // the neighbour merges in, now the large one fits and goes first
+    pool.deallocate(addrs[2]).unwrap();
+    assert!(large.woken());
+    assert!(small.poll().is_pending());
+    assert!(matches!(large.poll(), Poll::Ready(Ok(_))));
+
+    pool.deallocate(addrs[3]).unwrap();
+    assert!(small.woken());
+    assert!(matches!(small.poll(), Poll::Ready(Ok(_))));
+    assert_eq!(pool.waiters(), 0);
+}
+
+#[test]
+fn dropping_a_waiter_passes_its_turn_on() {
+    let (pool, addrs) = full_pool(128, 4);
+    let mut first = Task::new(pool.allocate_async(64));
+    let mut second = Task::new(pool.allocate_async(64));
+    assert!(first.poll().is_pending());
+    assert!(second.poll().is_pending());
+
+    pool.deallocate(addrs[0]).unwrap();
+    assert!(first.woken() && !second.woken());
+    drop(first);
+    assert!(second.woken());
+    assert_eq!(pool.waiters(), 1);
+    assert!(matches!(second.poll(), Poll::Ready(Ok(addr)) if addr == addrs[0]));
+    assert_eq!(pool.waiters(), 0);
+}
+
+#[test]
+fn requests_larger_than_the_heap_fail() {
+    let pool = AsyncAllocator::new(SubAllocator::new(1024));
+    let mut task = Task::new(pool.allocate_async(2048));
+    assert!(task.poll().is_ready());
+    assert_eq!(pool.waiters(), 0);
+
+    drop(task);
+    let sa = pool.into_inner();
+    assert_eq!(sa.free(), sa.capacity());
+}
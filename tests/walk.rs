@@ -0,0 +1,48 @@
+use suballoc::{BlockInfo, SubAllocator};
+
+fn free_offsets(sa: &SubAllocator) -> Vec<u32> {
+    let mut offsets = sa
+        .free_bins()
+        .flat_map(|(_, _, list)| list)
+        .collect::<Vec<_>>();
+    offsets.sort();
+    offsets
+}
+
+#[test]
+fn blocks_and_bins_follow_the_layout() {
+    let mut sa = SubAllocator::new(1024);
+    let [a, b, c] = [0; 3].map(|_| sa.allocate(64).unwrap());
+    sa.deallocate(b).unwrap();
+
+    let blocks = sa.blocks().collect::<Vec<_>>();
+    let layout = blocks
+        .iter()
+        .map(|block| (block.offset, block.used, block.prev_used, block.next_used))
+        .collect::<Vec<_>>();
+    let rest = blocks[3].offset;
+    // the heap edges count as used neighbours
+    assert_eq!(
+        layout,
+        [
+            (a, true, true, false),
+            (b, false, true, true),
+            (c, true, false, false),
+            (rest, false, true, true),
+        ]
+    );
+    // back to back, covering the heap
+    assert!(blocks.windows(2).all(|w| w[0].end() == w[1].offset));
+    assert_eq!(blocks[3].end(), 1024);
+    assert!(blocks[..3].iter().all(|block| block.size == blocks[0].size));
+    assert_eq!(sa.used_blocks().collect::<Vec<_>>(), [blocks[0], blocks[2]]);
+    assert_eq!(free_offsets(&sa), [b, rest]);
+
+    // freeing c merges it into both free neighbours
+    sa.deallocate(c).unwrap();
+    let blocks = sa.blocks().collect::<Vec<BlockInfo>>();
+    assert_eq!(blocks.len(), 2);
+    assert_eq!((blocks[1].offset, blocks[1].end()), (b, 1024));
+    assert!(!blocks[0].next_used && blocks[1].prev_used && blocks[1].next_used);
+    assert_eq!(free_offsets(&sa), [b]);
+}
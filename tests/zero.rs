@@ -0,0 +1,51 @@
+use suballoc::SubAllocator;
+
+#[test]
+fn fresh_blocks_are_zeroed() {
+    let mut sa = SubAllocator::new(4096);
+    // pristine, but the free block split off the front left its links in
+    // the first payload word
+    let a = sa.allocate(64).unwrap();
+    let b = sa.allocate_zeroed(200).unwrap();
+    assert!(sa.payload(b).unwrap().iter().all(|&byte| byte == 0));
+    sa.payload_mut(a).unwrap().fill(0xff);
+    sa.check_integrity().unwrap();
+}
+
+#[test]
+fn reused_blocks_are_zeroed() {
+    let mut sa = SubAllocator::new(4096);
+    let a = sa.allocate(2048).unwrap();
+    sa.payload_mut(a).unwrap().fill(0xff);
+    sa.deallocate(a).unwrap();
+
+    let c = sa.allocate_zeroed(200).unwrap();
+    assert_eq!(c, a);
+    assert!(sa.payload(c).unwrap().iter().all(|&byte| byte == 0));
+    sa.check_integrity().unwrap();
+}
+
+// guards repaint freed payloads with POISON
+#[cfg(not(feature = "guards"))]
+#[test]
+fn scrub_on_free_wipes_payloads() {
+    use suballoc::Storage;
+
+    for scrub in [false, true] {
+        let mut sa = SubAllocator::new(4096);
+        sa.set_scrub_on_free(scrub);
+        assert_eq!(sa.scrub_on_free(), scrub);
+        let a = sa.allocate(200).unwrap();
+        let _b = sa.allocate(16).unwrap();
+        sa.payload_mut(a).unwrap().fill(0xff);
+        sa.deallocate(a).unwrap();
+
+        // past the head and the free list links
+        let start = a as usize + 16;
+        let mem = unsafe { std::slice::from_raw_parts(sa.storage().mem_ptr(), 4096) };
+        let wiped = mem[start..a as usize + 8 + 200]
+            .iter()
+            .all(|&byte| byte == 0);
+        assert_eq!(wiped, scrub);
+    }
+}